@@ -0,0 +1,192 @@
+use crate::util::sc_to_ucc;
+
+use std::collections::HashMap;
+
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, LitStr, Token,
+};
+
+/// The arguments passed to the `kaitai_source` macro, i.e. the path to the ksy file followed by
+/// any options.
+///
+/// ```ignore
+/// #[kaitai_source("example.ksy", borrowed)]
+/// ```
+///
+/// The `opaque_types` option can map the names of opaque types to Rust types, and say that they
+/// implement `Eq` and `Ord`.
+///
+/// ```ignore
+/// #[kaitai_source("example.ksy", opaque_types(png_chunk = crate::png::Chunk: Eq, gif: Eq))]
+/// ```
+pub struct MacroArgs {
+    pub path: LitStr,
+    pub options: Options,
+    /// The Rust types of opaque types given by the `opaque_types` option, keyed by the upper
+    /// camel case names of the types.
+    pub opaque_types: HashMap<String, OpaqueType>,
+}
+
+/// The Rust type of an opaque type given by the `opaque_types` option, e.g.
+/// `png_chunk = crate::png::Chunk: Eq`.
+#[derive(Clone, Debug, Default)]
+pub struct OpaqueType {
+    /// The path of the type, or `None` for the type in scope with the name of the opaque type.
+    pub path: Option<syn::Path>,
+    /// Whether the type implements `Eq` and `Ord`, so that the types containing it derive them.
+    pub eq: bool,
+}
+
+/// Options changing the code generated for a ksy file. These apply to all types in the file.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Options {
+    /// Byte arrays and strings borrow from the bytes being parsed rather than being copied.
+    pub borrowed: bool,
+    /// Repeated attributes of user defined types are parsed lazily, where possible, like
+    /// attributes with `-lazy: true`.
+    pub lazy: bool,
+    /// Enum attributes fail with `NoEnumMatch` on values missing from the enum, rather than
+    /// reading them as `Unknown`, like attributes with `-strict: true`.
+    pub strict_enums: bool,
+    /// Byte arrays are represented as base64 strings rather than hexadecimal strings with the
+    /// `serde` feature.
+    pub base64_bytes: bool,
+    /// The offsets every attribute is read from are recorded, like with `ks-debug: true` in the
+    /// meta section.
+    pub ks_debug: bool,
+    /// Types that aren't defined in the ksy file refer to Rust types, like with
+    /// `ks-opaque-types: true` in the meta section.
+    pub opaque_types: bool,
+}
+
+impl Parse for MacroArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut options = Options::default();
+        let mut opaque_types = HashMap::new();
+
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            for option in Punctuated::<MacroOption, Token![,]>::parse_terminated(input)? {
+                match option.ident.to_string().as_ref() {
+                    "borrowed" => options.borrowed = true,
+                    "lazy" => options.lazy = true,
+                    "strict_enums" => options.strict_enums = true,
+                    "base64_bytes" => options.base64_bytes = true,
+                    "ks_debug" => options.ks_debug = true,
+                    "opaque_types" => options.opaque_types = true,
+                    _ => return Err(syn::Error::new(option.ident.span(), "unknown option")),
+                }
+                if !option.opaque_types.is_empty() && option.ident != "opaque_types" {
+                    return Err(syn::Error::new(
+                        option.ident.span(),
+                        "only opaque_types takes arguments",
+                    ));
+                }
+                for (name, ty) in option.opaque_types {
+                    opaque_types.insert(sc_to_ucc(name.to_string()), ty);
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            options,
+            opaque_types,
+        })
+    }
+}
+
+/// An option passed to the `kaitai_source` macro, optionally followed by the names of opaque
+/// types and their Rust types, e.g. `opaque_types(png_chunk = crate::png::Chunk, gif: Eq)`.
+struct MacroOption {
+    ident: Ident,
+    opaque_types: Vec<(Ident, OpaqueType)>,
+}
+
+impl Parse for MacroOption {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        let mut opaque_types = Vec::new();
+        if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            let pairs = Punctuated::<(Ident, OpaqueType), Token![,]>::parse_terminated_with(
+                &content,
+                |input| {
+                    let name = input.parse()?;
+                    let mut ty = OpaqueType::default();
+                    if input.parse::<Option<Token![=]>>()?.is_some() {
+                        ty.path = Some(input.parse()?);
+                    }
+                    if input.parse::<Option<Token![:]>>()?.is_some() {
+                        let eq: Ident = input.parse()?;
+                        if eq != "Eq" {
+                            return Err(syn::Error::new(eq.span(), "expected `Eq`"));
+                        }
+                        ty.eq = true;
+                    }
+                    Ok((name, ty))
+                },
+            )?;
+            opaque_types.extend(pairs);
+        }
+        Ok(Self {
+            ident,
+            opaque_types,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        let args: MacroArgs = syn::parse_str(r#""example.ksy""#).unwrap();
+        assert_eq!(args.path.value(), "example.ksy");
+        assert!(!args.options.borrowed);
+
+        let args: MacroArgs = syn::parse_str(r#""example.ksy", borrowed,"#).unwrap();
+        assert!(args.options.borrowed);
+        assert!(!args.options.lazy);
+
+        let args: MacroArgs = syn::parse_str(r#""example.ksy", lazy"#).unwrap();
+        assert!(args.options.lazy);
+        assert!(!args.options.strict_enums);
+
+        let args: MacroArgs = syn::parse_str(r#""example.ksy", lazy, strict_enums"#).unwrap();
+        assert!(args.options.strict_enums);
+        assert!(!args.options.base64_bytes);
+
+        let args: MacroArgs = syn::parse_str(r#""example.ksy", base64_bytes"#).unwrap();
+        assert!(args.options.base64_bytes);
+        assert!(!args.options.ks_debug);
+
+        let args: MacroArgs = syn::parse_str(r#""example.ksy", ks_debug"#).unwrap();
+        assert!(args.options.ks_debug);
+        assert!(!args.options.opaque_types);
+
+        let args: MacroArgs = syn::parse_str(
+            r#""example.ksy", opaque_types(png_chunk = crate::png::Chunk, gif = Gif: Eq, bmp: Eq), lazy"#,
+        )
+        .unwrap();
+        assert!(args.options.opaque_types);
+        assert!(args.options.lazy);
+        let path: syn::Path = syn::parse_str("crate::png::Chunk").unwrap();
+        assert_eq!(args.opaque_types["PngChunk"].path, Some(path));
+        assert!(!args.opaque_types["PngChunk"].eq);
+        assert!(args.opaque_types["Gif"].eq);
+        assert_eq!(args.opaque_types["Bmp"].path, None);
+        assert!(args.opaque_types["Bmp"].eq);
+        assert_eq!(args.opaque_types.len(), 3);
+
+        assert!(syn::parse_str::<MacroArgs>(r#""example.ksy", opaque_types(gif: Hash)"#).is_err());
+
+        assert!(syn::parse_str::<MacroArgs>(r#""example.ksy", lazy(a = A)"#).is_err());
+
+        assert!(syn::parse_str::<MacroArgs>(r#""example.ksy", unknown"#).is_err());
+    }
+}
@@ -1,7 +1,5 @@
 use crate::de::{data::IntegerValue, doc::Doc};
 
-use std::collections::HashMap;
-
 use serde::{de, Deserialize, Deserializer};
 
 #[derive(Clone, Debug, Deserialize)]
@@ -33,6 +31,14 @@ pub struct Attr {
     pub pos: Option<IntegerValue>,
     pub io: Option<String>,
     pub value: Option<String>,
+    pub valid: Option<Valid>,
+    /// Not part of Kaitai Struct, hence the leading dash. Makes a repeated attribute parse lazily.
+    #[serde(rename = "-lazy")]
+    pub lazy: bool,
+    /// Not part of Kaitai Struct, hence the leading dash. Makes an enum attribute fail on values
+    /// missing from the enum, or not, overriding the `strict_enums` option.
+    #[serde(rename = "-strict")]
+    pub strict: Option<bool>,
 }
 
 impl Default for Attr {
@@ -59,6 +65,9 @@ impl Default for Attr {
             pos: None,
             io: None,
             value: None,
+            valid: None,
+            lazy: false,
+            strict: None,
         }
     }
 }
@@ -106,6 +115,88 @@ where
     Ok(Some(deserializer.deserialize_any(ContentsVisitor)?))
 }
 
+/// A scalar of the KSY file, e.g. the key of a case or a `valid` value, as a string. Integers and
+/// booleans are converted to strings, with the integers in decimal.
+#[derive(Clone, Debug)]
+pub struct Scalar(pub String);
+
+impl<'de> Deserialize<'de> for Scalar {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScalarVisitor;
+
+        impl<'de> de::Visitor<'de> for ScalarVisitor {
+            type Value = Scalar;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("string, integer or boolean")
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Scalar(value.to_string()))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Scalar(value.to_string()))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Scalar(value.to_string()))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Scalar(value.to_owned()))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+/// Deserializes the cases of a switch in the order they are written, with their keys as strings.
+/// Keys can be integers or booleans as well as strings, see [`Scalar`].
+pub fn deserialize_cases<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct CasesVisitor;
+
+    impl<'de> de::Visitor<'de> for CasesVisitor {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("map of cases")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::MapAccess<'de>,
+        {
+            let mut cases = Vec::with_capacity(map.size_hint().unwrap_or(0));
+            while let Some((Scalar(key), ty)) = map.next_entry()? {
+                cases.push((key, ty));
+            }
+            Ok(cases)
+        }
+    }
+
+    deserializer.deserialize_map(CasesVisitor)
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(untagged)]
 pub enum AttrType {
@@ -113,7 +204,22 @@ pub enum AttrType {
     #[serde(rename_all = "kebab-case")]
     Switch {
         switch_on: String,
-        cases: HashMap<String, String>,
+        #[serde(deserialize_with = "deserialize_cases")]
+        cases: Vec<(String, String)>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Valid {
+    Eq(Scalar),
+    #[serde(rename_all = "kebab-case")]
+    Constraints {
+        eq: Option<Scalar>,
+        min: Option<Scalar>,
+        max: Option<Scalar>,
+        any_of: Option<Vec<Scalar>>,
+        expr: Option<String>,
     },
 }
 
@@ -1,3 +1,4 @@
+use quote::ToTokens;
 use serde::{de, Deserializer};
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -13,11 +14,37 @@ impl quote::ToTokens for IntegerValue {
             IntegerValue::Variable(id) => {
                 proc_macro2::Ident::new(id, proc_macro2::Span::call_site()).into_token_stream()
             }
-            IntegerValue::Literal(value) => quote::quote! { #value },
+            IntegerValue::Literal(value) => {
+                proc_macro2::Literal::u64_unsuffixed(*value).into_token_stream()
+            }
         });
     }
 }
 
+impl IntegerValue {
+    /// Returns a [`TokenStream`](proc_macro2::TokenStream) of the value, accessed through `self`
+    /// if `on_self` is set.
+    pub fn tokens(&self, on_self: bool) -> proc_macro2::TokenStream {
+        if on_self {
+            self.self_tokens()
+        } else {
+            self.into_token_stream()
+        }
+    }
+
+    /// Returns a [`TokenStream`](proc_macro2::TokenStream) of the value accessed through `self`,
+    /// for use in methods of the generated struct rather than in its constructor.
+    pub fn self_tokens(&self) -> proc_macro2::TokenStream {
+        match self {
+            IntegerValue::Variable(id) => {
+                let id = proc_macro2::Ident::new(id, proc_macro2::Span::call_site());
+                quote::quote! { self.#id }
+            }
+            IntegerValue::Literal(_) => self.into_token_stream(),
+        }
+    }
+}
+
 pub fn deserialize_string_or_seq<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
@@ -7,12 +7,73 @@ use serde::{
     Deserialize, Deserializer,
 };
 
-#[derive(Clone, Debug, serde::Deserialize)]
-pub struct Enum(pub HashMap<u64, EnumValue>);
+/// The values of an enum, keyed by their integer values. The keys are `i128`s so that they can be
+/// any `u64` or `i64`. Boolean keys are read as 0 and 1.
+#[derive(Clone, Debug)]
+pub struct Enum(pub HashMap<i128, EnumValue>);
+
+impl<'de> Deserialize<'de> for Enum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(PartialEq, Eq, Hash)]
+        struct Key(i128);
+
+        impl<'de> Deserialize<'de> for Key {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct KeyVisitor;
+
+                impl<'de> de::Visitor<'de> for KeyVisitor {
+                    type Value = Key;
+
+                    fn expecting(
+                        &self,
+                        formatter: &mut std::fmt::Formatter<'_>,
+                    ) -> std::fmt::Result {
+                        formatter.write_str("integer or boolean")
+                    }
+
+                    fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        Ok(Key(value.into()))
+                    }
+
+                    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        Ok(Key(value.into()))
+                    }
+
+                    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        Ok(Key(value.into()))
+                    }
+                }
+
+                deserializer.deserialize_any(KeyVisitor)
+            }
+        }
+
+        let values = HashMap::<Key, EnumValue>::deserialize(deserializer)?;
+        Ok(Self(values.into_iter().map(|(Key(k), v)| (k, v)).collect()))
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct EnumValue {
     pub id: String,
+    /// The identifier of the value in the original documentation of the format, if it differs
+    /// from `id`.
+    pub orig_id: Option<String>,
     pub doc: Doc,
 }
 
@@ -25,6 +86,8 @@ impl<'de> Deserialize<'de> for EnumValue {
         #[serde(field_identifier, rename_all = "kebab-case")]
         enum Field {
             Id,
+            #[serde(rename = "-orig-id")]
+            OrigId,
             Doc,
             DocRef,
         }
@@ -44,6 +107,7 @@ impl<'de> Deserialize<'de> for EnumValue {
             {
                 Ok(EnumValue {
                     id: id.to_owned(),
+                    orig_id: None,
                     doc: Doc::default(),
                 })
             }
@@ -54,6 +118,7 @@ impl<'de> Deserialize<'de> for EnumValue {
             {
                 Ok(EnumValue {
                     id,
+                    orig_id: None,
                     doc: Doc::default(),
                 })
             }
@@ -63,6 +128,7 @@ impl<'de> Deserialize<'de> for EnumValue {
                 V: MapAccess<'de>,
             {
                 let mut id = None;
+                let mut orig_id = None;
                 let mut doc = None;
                 let mut doc_ref = None;
 
@@ -74,6 +140,12 @@ impl<'de> Deserialize<'de> for EnumValue {
                             }
                             id = Some(map.next_value()?);
                         }
+                        Field::OrigId => {
+                            if orig_id.is_some() {
+                                return Err(de::Error::duplicate_field("-orig-id"));
+                            }
+                            orig_id = Some(map.next_value()?);
+                        }
                         Field::Doc => {
                             if doc.is_some() {
                                 return Err(de::Error::duplicate_field("doc"));
@@ -106,7 +178,7 @@ impl<'de> Deserialize<'de> for EnumValue {
 
                 let doc = Doc { doc, doc_ref };
 
-                Ok(EnumValue { id, doc })
+                Ok(EnumValue { id, orig_id, doc })
             }
         }
 
@@ -1,4 +1,4 @@
-use crate::de::data::deserialize_string_or_seq;
+use crate::de::{attr::deserialize_cases, data::deserialize_string_or_seq};
 
 use serde::Deserialize;
 
@@ -14,7 +14,18 @@ pub struct Meta {
     pub imports: Vec<String>,
     pub encoding: Option<String>,
     #[serde(rename = "endian")]
-    pub endianness: Option<Endianness>,
+    pub endianness: Option<Endian>,
+    /// Not part of Kaitai Struct, hence the leading dash. The traits derived for the type and
+    /// the types and enums it contains, instead of the ones derived by the annotated struct.
+    #[serde(rename = "-derive", deserialize_with = "deserialize_derive")]
+    pub derive: Option<Vec<String>>,
+}
+
+fn deserialize_derive<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_string_or_seq(deserializer).map(Some)
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -29,11 +40,32 @@ pub struct MetaDoc {
     pub license: String,
 }
 
+/// The `endian` key, which is either fixed or switches on a value read before the type.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Endian {
+    Fixed(Endianness),
+    Switch {
+        #[serde(rename = "switch-on")]
+        switch_on: String,
+        #[serde(deserialize_with = "deserialize_cases")]
+        cases: Vec<(String, String)>,
+    },
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Endianness {
     Le,
     Be,
+    /// The endianness of a type with a switching `endian`, or of the types it contains, decided
+    /// when it is read.
+    #[serde(skip)]
+    Runtime,
+    /// The endianness of a type that neither has nor inherits an `endian` key, which can only
+    /// read integers and floats of a single byte.
+    #[serde(skip)]
+    Unspecified,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
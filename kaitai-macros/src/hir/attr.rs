@@ -1,33 +1,263 @@
 use crate::{
-    de,
-    hir::{doc::Doc, meta::Endianness},
+    args::{OpaqueType, Options},
+    de::{self, attr::Scalar},
+    hir::{
+        doc::Doc,
+        meta::{EndianBase, EndianSwitch, Endianness},
+        ty::{derive_attribute, serde_attributes, struct_derives},
+    },
     util::sc_to_ucc,
 };
 
+use std::collections::{HashMap, HashSet};
+
 use proc_macro2::{Ident, Span, TokenStream};
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 
 pub use crate::de::data::IntegerValue;
 
 #[derive(Clone, Debug)]
 pub struct Attributes(Vec<Attribute>);
 
-impl TryFrom<(Option<de::meta::MetaDoc>, Vec<de::attr::Attr>)> for Attributes {
+/// Attributes are created from the doc and options of their type, the identifier of their type,
+/// and their definitions.
+impl
+    TryFrom<(
+        Option<de::meta::MetaDoc>,
+        Options,
+        &Ident,
+        Vec<de::attr::Attr>,
+    )> for Attributes
+{
     type Error = ();
 
     fn try_from(
-        (meta_doc, attrs): (Option<de::meta::MetaDoc>, Vec<de::attr::Attr>),
+        (meta_doc, options, owner, attrs): (
+            Option<de::meta::MetaDoc>,
+            Options,
+            &Ident,
+            Vec<de::attr::Attr>,
+        ),
     ) -> Result<Self, Self::Error> {
-        Ok(Self(
-            attrs
-                .into_iter()
-                .map(|a| (meta_doc.clone(), a).try_into())
-                .collect::<Result<Vec<_>, _>>()?,
-        ))
+        let explicitly_lazy: Vec<_> = attrs.iter().map(|a| a.lazy).collect();
+        let mut attrs = attrs
+            .into_iter()
+            .map(|a| (meta_doc.clone(), options, owner, a).try_into())
+            .collect::<Result<Vec<Attribute>, _>>()?;
+
+        // The attributes after a lazy attribute couldn't be found without parsing it, so only the
+        // last attribute can be lazy.
+        let len = attrs.len();
+        for (a, explicit) in attrs
+            .iter_mut()
+            .zip(explicitly_lazy)
+            .take(len.saturating_sub(1))
+        {
+            if explicit {
+                panic!("lazy attribute {} isn't the last attribute", a.id);
+            }
+            a.lazy = false;
+        }
+
+        // The integer cases of a switch have to fit in the type of the attribute switched on.
+        let int_types: HashMap<Ident, BuiltInType> = attrs
+            .iter()
+            .filter_map(|a| match &a.logic {
+                Logic::Type(Type::BuiltIn { ty, en: None }) if a.repeat.is_none() => {
+                    Some((a.id.clone(), ty.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for a in attrs.iter_mut() {
+            if let Logic::Switch(switch) = &mut a.logic {
+                if let Some(ty) = int_types.get(&switch.on) {
+                    switch.check_int_cases(&a.id, ty);
+                }
+            }
+        }
+
+        Ok(Self(attrs))
     }
 }
 
 impl Attributes {
+    /// Returns whether any of the attributes borrow from the bytes being parsed, given the names
+    /// of the user defined types that borrow.
+    pub fn borrows(&self, borrowing: &HashSet<String>) -> bool {
+        self.0.iter().any(|a| a.borrows(borrowing))
+    }
+
+    /// Marks the attributes of user defined types that borrow from the bytes being parsed.
+    pub fn set_borrowing(&mut self, borrowing: &HashSet<String>) {
+        for a in self.0.iter_mut() {
+            match &mut a.logic {
+                Logic::Type(Type::UserDefined { id, borrowed }) => {
+                    *borrowed = borrowing.contains(&id.to_string());
+                    if *borrowed && a.lazy {
+                        // TODO: lazy attributes of types that borrow
+                        panic!("lazy attribute {} has a type that borrows", a.id);
+                    }
+                }
+                Logic::Switch(switch) => switch.set_borrowing(borrowing),
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks the cases of the switches on enums against `enums`, the ids of the variants of
+    /// every enum keyed by the name of their enum. Panics if a case names a missing variant, or if
+    /// a switch without a default case doesn't cover every variant.
+    pub fn check_switches(&self, enums: &HashMap<String, Vec<String>>) {
+        for a in self.0.iter() {
+            if let Logic::Switch(switch) = &a.logic {
+                let missing = switch.missing_variants(enums);
+                if !missing.is_empty() {
+                    panic!(
+                        "switch of attribute {} doesn't cover {}, add a `_` case to ignore them",
+                        a.id,
+                        missing.join(", ")
+                    );
+                }
+            }
+        }
+    }
+
+    /// Replaces the user defined types that aren't in `defined`, the names of the types in the
+    /// ksy file, with opaque types. These are the Rust types in `types`, keyed by the names of
+    /// the types, or otherwise the types in scope with the same names. Panics on undefined types
+    /// unless `enabled`, i.e. opaque types are allowed.
+    pub fn set_opaque(
+        &mut self,
+        enabled: bool,
+        defined: &HashSet<String>,
+        types: &HashMap<String, OpaqueType>,
+    ) {
+        for a in self.0.iter_mut() {
+            match &mut a.logic {
+                Logic::Type(ty) => ty.set_opaque(&a.id, enabled, defined, types),
+                Logic::Switch(switch) => {
+                    for (_, ty) in switch.variants.iter_mut() {
+                        ty.set_opaque(&a.id, enabled, defined, types);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sets the endianness of the attributes whose types switch their endianness on an attribute
+    /// of the type reading them, given these types keyed by their names. If the attribute
+    /// switched on isn't one of these attributes, reading the attribute fails to compile.
+    pub fn set_endian_switches(&mut self, owner: &Ident, switches: &HashMap<String, EndianSwitch>) {
+        let ids: Vec<_> = self.0.iter().map(|a| a.id.clone()).collect();
+        for a in self.0.iter_mut() {
+            let ty = match &a.logic {
+                Logic::Type(Type::UserDefined { id, .. }) => id,
+                _ => continue,
+            };
+            let endian = match switches.get(&ty.to_string()) {
+                Some(endian) if endian.base() == EndianBase::Parent => endian,
+                _ => continue,
+            };
+            a.endian = Some(if !ids.contains(endian.on()) {
+                Err(format!(
+                    "the endianness of {} switches on {}, which isn't an attribute of {}",
+                    ty,
+                    endian.on(),
+                    owner
+                ))
+            } else if a.lazy {
+                // TODO: lazy attributes of types with a switching endianness
+                Err(format!(
+                    "lazy attributes of types with a switching endianness aren't supported yet, \
+                     used by attribute {}",
+                    a.id
+                ))
+            } else {
+                Ok(endian.clone())
+            });
+        }
+    }
+
+    /// Makes the attribute switched on by `endian` decide the endianness of the type `ty` once it
+    /// is read, or of the type owning the attributes, `owner`, if `ty` is `None`. Returns an error
+    /// message if there is no such attribute, or it can't be switched on.
+    pub fn set_endian_decision(
+        &mut self,
+        owner: &Ident,
+        ty: Option<&Ident>,
+        endian: &EndianSwitch,
+    ) -> Result<(), String> {
+        let a = self
+            .0
+            .iter_mut()
+            .find(|a| &a.id == endian.on())
+            .ok_or_else(|| {
+                format!(
+                    "the endianness of {} switches on {}, which isn't an attribute of {}",
+                    ty.unwrap_or(owner),
+                    endian.on(),
+                    owner
+                )
+            })?;
+        let is_value = matches!(
+            a.logic,
+            Logic::Type(Type::BuiltIn { .. }) | Logic::Size(_) | Logic::Str(_)
+        );
+        if a.repeat.is_some() || a.lazy || !is_value {
+            return Err(format!(
+                "the endianness of {} switches on {}, which isn't a single value",
+                ty.unwrap_or(owner),
+                a.id
+            ));
+        }
+        a.decides_endian.push((ty.cloned(), endian.clone()));
+        Ok(())
+    }
+
+    /// Returns whether any of the attributes are opaque types, given the names of the user
+    /// defined types containing opaque types.
+    pub fn has_opaque(&self, opaque: &HashSet<String>) -> bool {
+        self.0.iter().any(|a| match &a.logic {
+            Logic::Type(ty) => ty.is_opaque(opaque),
+            Logic::Switch(switch) => switch.variants.iter().any(|(_, ty)| ty.is_opaque(opaque)),
+            _ => false,
+        })
+    }
+
+    /// Returns the definitions of the enums holding the values of switch attributes, deriving
+    /// `user_derives` as well.
+    pub fn switch_definitions<'a>(
+        &'a self,
+        user_derives: &'a [syn::Path],
+    ) -> impl Iterator<Item = TokenStream> + 'a {
+        self.0.iter().filter_map(move |a| match &a.logic {
+            Logic::Switch(switch) => Some(switch.definition(&a.id, user_derives)),
+            _ => None,
+        })
+    }
+
+    /// Returns whether any of the attributes are floats, given the names of the user defined
+    /// types containing floats.
+    pub fn has_floats(&self, floats: &HashSet<String>) -> bool {
+        self.0.iter().any(|a| match &a.logic {
+            Logic::Type(ty) => ty.is_float(floats),
+            Logic::Switch(switch) => switch.has_floats(floats),
+            _ => false,
+        })
+    }
+
+    /// Marks the switches that can't derive `Eq` and `Ord`, given the names of the user defined
+    /// types containing floats.
+    pub fn set_floats(&mut self, floats: &HashSet<String>) {
+        for a in self.0.iter_mut() {
+            if let Logic::Switch(switch) = &mut a.logic {
+                switch.eq = !switch.has_floats(floats);
+            }
+        }
+    }
+
     pub fn field_definitions(&self) -> impl Iterator<Item = TokenStream> + '_ {
         self.0
             .iter()
@@ -38,10 +268,56 @@ impl Attributes {
     pub fn variable_assignments(
         &self,
         endianness: Endianness,
+        is_async: bool,
+    ) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0
+            .iter()
+            .map(move |a| a.variable_assignment(endianness, is_async))
+    }
+
+    pub fn read_statements(
+        &self,
+        endianness: Endianness,
+    ) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(move |a| a.read_statement(endianness))
+    }
+
+    pub fn write_statements(
+        &self,
+        endianness: Endianness,
     ) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(move |a| a.write_statement(endianness))
+    }
+
+    pub fn check_statements(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(|a| a.check_statement())
+    }
+
+    pub fn visit_statements(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(|a| a.visit_statement())
+    }
+
+    pub fn partial_field_definitions(&self) -> impl Iterator<Item = TokenStream> + '_ {
         self.0
             .iter()
-            .map(move |a| a.variable_assignment(endianness))
+            .filter(|a| a.is_stored())
+            .map(|a| a.partial_field_definition())
+    }
+
+    pub fn partial_statements(
+        &self,
+        endianness: Endianness,
+    ) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(move |a| a.partial_statement(endianness))
+    }
+
+    /// Adds the types of the enum attributes to `types`, keyed by the name of their enum.
+    pub fn enum_types(&self, types: &mut HashMap<String, Vec<BuiltInType>>) {
+        for a in self.0.iter() {
+            if let Logic::Type(Type::BuiltIn { ty, en: Some(en) }) = &a.logic {
+                types.entry(en.to_string()).or_default().push(ty.clone());
+            }
+        }
     }
 
     pub fn field_assignments(&self) -> impl Iterator<Item = &Ident> {
@@ -54,7 +330,20 @@ pub struct Attribute {
     id: Ident,
     doc: Doc,
     repeat: Option<Repeat>,
+    valid: Option<Valid>,
     logic: Logic,
+    options: Options,
+    /// Whether the attribute is a `::kaitai::LazyRepeat` rather than a `Vec`.
+    lazy: bool,
+    /// Whether values missing from the enum of an enum attribute fail, rather than being read as
+    /// `Unknown`.
+    strict: bool,
+    /// The endianness of the type of the attribute, if it switches on an attribute of the type
+    /// reading it, i.e. the owner of this attribute, or an error message if it can't be decided.
+    endian: Option<Result<EndianSwitch, String>>,
+    /// The endianness switches on this attribute, decided once it is read, and the types they
+    /// belong to, `None` being the owner of this attribute.
+    decides_endian: Vec<(Option<Ident>, EndianSwitch)>,
 }
 
 impl Attribute {
@@ -62,12 +351,135 @@ impl Attribute {
         match &self.logic {
             Logic::FixedContents(_) => false,
             Logic::Type(_) => true,
-            Logic::Switch { .. } => true,
+            Logic::Switch(_) => true,
             Logic::Size(_) => true,
+            Logic::Str(_) => true,
             Logic::Process(_) => true,
         }
     }
 
+    fn borrows(&self, borrowing: &HashSet<String>) -> bool {
+        match &self.logic {
+            Logic::Size(_) | Logic::Str(_) => self.options.borrowed,
+            Logic::Type(Type::UserDefined { id, .. }) => borrowing.contains(&id.to_string()),
+            Logic::Switch(switch) => switch.borrows(borrowing),
+            _ => false,
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading the bytes of a byte array or string. If `on_self` is set,
+    /// sizes are accessed through `self` rather than through variables.
+    fn bytes_expr(&self, size: &Size, on_self: bool, is_async: bool) -> TokenStream {
+        let aw = await_tokens(is_async);
+        match (size, self.options.borrowed) {
+            (Size::Fixed(count), borrowed) => {
+                let count = count.tokens(on_self);
+                if borrowed {
+                    quote! { buf.read_bytes_ref(#count as usize)? }
+                } else {
+                    quote! {
+                        {
+                            parse_state.alloc(#count as u64)?;
+                            buf.read_bytes(#count as usize)#aw?
+                        }
+                    }
+                }
+            }
+            (Size::Eos, false) => {
+                let alloc_check = Size::eos_alloc_check(is_async);
+                quote! {
+                    {
+                        #alloc_check
+                        buf.read_bytes_full()#aw?
+                    }
+                }
+            }
+            (Size::Eos, true) => quote! { buf.read_bytes_full_ref()? },
+            (Size::Term { term, .. }, false) => {
+                let flags = size.flags();
+                // The size isn't known up front, but the bytes are only allocated as they are
+                // read.
+                quote! {
+                    {
+                        let bytes = buf.read_bytes_term(#term, #flags)#aw?;
+                        parse_state.alloc(bytes.len() as u64)?;
+                        bytes
+                    }
+                }
+            }
+            (Size::Term { term, .. }, true) => {
+                let flags = size.flags();
+                quote! { buf.read_bytes_term_ref(#term, #flags)? }
+            }
+            (
+                Size::Truncated {
+                    size: inner, term, ..
+                },
+                false,
+            ) => {
+                let bytes = self.bytes_expr(inner, on_self, is_async);
+                let flags = size.flags();
+                quote! {
+                    {
+                        let mut bytes = #bytes;
+                        let len = (#flags).truncate(&bytes, #term).len();
+                        bytes.truncate(len);
+                        bytes
+                    }
+                }
+            }
+            (
+                Size::Truncated {
+                    size: inner, term, ..
+                },
+                true,
+            ) => {
+                let bytes = self.bytes_expr(inner, on_self, is_async);
+                let flags = size.flags();
+                quote! { (#flags).truncate(#bytes, #term) }
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] creating the `::kaitai::LazyRepeat` of a lazy `Attribute`, which
+    /// starts at the current position, and moving the stream past its elements, so that whatever
+    /// follows the type owning the `Attribute` is read from the right position. Elements repeated
+    /// until the end of the stream are skipped by seeking to the end, and the others are parsed
+    /// once without being kept, as their size isn't known. If `on_self` is set, the count is
+    /// accessed through `self` rather than through a variable.
+    fn lazy_expr(&self, endianness: Endianness, on_self: bool, is_async: bool) -> TokenStream {
+        let aw = await_tokens(is_async);
+        let (count, skip) = match &self.repeat {
+            Some(Repeat::Expr(count)) => {
+                let count = count.tokens(on_self);
+                let expr = self.element_expr(endianness, on_self, is_async);
+                (
+                    quote! { ::std::option::Option::Some(#count as u64) },
+                    quote! {
+                        for _ in 0..#count {
+                            #expr;
+                        }
+                    },
+                )
+            }
+            _ if is_async => (
+                quote! { ::std::option::Option::None },
+                quote! { ::kaitai::__private::AsyncSeekExt::seek(buf, ::std::io::SeekFrom::End(0)).await?; },
+            ),
+            _ => (
+                quote! { ::std::option::Option::None },
+                quote! { ::std::io::Seek::seek(buf, ::std::io::SeekFrom::End(0))?; },
+            ),
+        };
+        quote! {
+            {
+                let start = buf.pos()#aw?;
+                #skip
+                ::kaitai::LazyRepeat::new(start, #count)
+            }
+        }
+    }
+
     /// Returns a [`TokenStream`] containing the definition of the struct field
     /// containing the `Attribute`.
     ///
@@ -120,25 +532,82 @@ impl Attribute {
     /// Hence, this method return an empty [`TokenStream`] if the attribute has fixed
     /// contents.
     pub fn field_definition(&self) -> TokenStream {
-        let mut ty = match &self.logic {
-            Logic::FixedContents(_) => return TokenStream::new(),
-            Logic::Type(ty) => ty.ty(),
-            Logic::Switch { .. } => todo!(),
-            Logic::Size(_) => quote! { ::std::vec::Vec<u8> },
-            Logic::Process(_) => todo!(),
-        };
-        if self.repeat.is_some() {
-            ty = quote! { ::std::vec::Vec<#ty> };
+        if let Logic::FixedContents(_) = &self.logic {
+            return TokenStream::new();
         }
-
+        let ty = self.field_type();
         let doc = &self.doc;
+        let serde = self.serde_attribute();
         let id = &self.id;
         quote! {
             #doc
+            #serde
             pub #id: #ty
         }
     }
 
+    /// Returns the attribute setting the serde representation of the field containing the
+    /// `Attribute`, with the `serde` feature. Byte arrays are represented as hexadecimal strings,
+    /// or as base64 strings with the `base64_bytes` option.
+    fn serde_attribute(&self) -> TokenStream {
+        if !cfg!(feature = "serde") || !matches!(self.logic, Logic::Size(_) | Logic::Process(_)) {
+            return TokenStream::new();
+        }
+        let with = if self.options.base64_bytes {
+            "::kaitai::__private::serde_repr::base64"
+        } else {
+            "::kaitai::__private::serde_repr::hex"
+        };
+        quote! { #[serde(with = #with)] }
+    }
+
+    /// Returns a [`TokenStream`] representing the definition of the field of the partially
+    /// created struct containing the `Attribute`. The field is `None` until the `Attribute` starts
+    /// being read, and user defined types are partially created themselves.
+    pub fn partial_field_definition(&self) -> TokenStream {
+        let ty = match &self.logic {
+            Logic::Type(ty @ (Type::UserDefined { .. } | Type::Opaque { .. })) if !self.lazy => {
+                let ty = ty.path();
+                let ty = quote! { <#ty as ::kaitai::KaitaiStruct>::Partial };
+                if self.repeat.is_some() {
+                    quote! { ::std::vec::Vec<#ty> }
+                } else {
+                    ty
+                }
+            }
+            _ => self.field_type(),
+        };
+
+        let doc = &self.doc;
+        let serde = self.serde_attribute();
+        let id = &self.id;
+        quote! {
+            #doc
+            #serde
+            pub #id: ::std::option::Option<#ty>
+        }
+    }
+
+    /// Returns a [`TokenStream`] of the type of the field containing the `Attribute`.
+    fn field_type(&self) -> TokenStream {
+        let mut ty = match &self.logic {
+            Logic::FixedContents(_) => unreachable!(),
+            Logic::Type(ty) => ty.ty(),
+            Logic::Switch(switch) => switch.ty(),
+            Logic::Size(_) if self.options.borrowed => quote! { &'a [u8] },
+            Logic::Size(_) => quote! { ::std::vec::Vec<u8> },
+            Logic::Str(_) if self.options.borrowed => quote! { &'a str },
+            Logic::Str(_) => quote! { ::std::string::String },
+            Logic::Process(_) => unsupported(&self.id, "process"),
+        };
+        if self.lazy {
+            ty = quote! { ::kaitai::LazyRepeat<#ty> };
+        } else if self.repeat.is_some() {
+            ty = quote! { ::std::vec::Vec<#ty> };
+        }
+        ty
+    }
+
     /// Returns a [`TokenStream`] representing the assignment of the variable
     /// containing the `Attribute`.
     ///
@@ -165,7 +634,7 @@ impl Attribute {
     /// ```
     /// results in
     /// ```ignore
-    /// let example_attr = ExampleType::new(buf)?;
+    /// let example_attr = <ExampleType as ::kaitai::KaitaiStruct>::new_with(buf, parse_state)?;
     /// ```
     ///
     /// Note that the name of the type is converted into upper camel case.
@@ -179,6 +648,12 @@ impl Attribute {
     /// ```
     /// results in
     /// ```ignore
+    /// let example_attr = ExampleEnum::from_value(buf.read_u4le()?);
+    /// ```
+    ///
+    /// Values missing from the enum are read as `ExampleEnum::Unknown`, unless the attribute is
+    /// strict, in which case they fail:
+    /// ```ignore
     /// let example_attr = ExampleEnum::n(buf.read_u4le()?).ok_or(::kaitai::error::Error::NoEnumMatch)?;
     /// ```
     ///
@@ -197,20 +672,50 @@ impl Attribute {
     /// buf.ensure_fixed_contents("glTF".as_bytes())?;
     /// ```
     ///
-    pub fn variable_assignment(&self, endianness: Endianness) -> TokenStream {
-        let mut expr = match &self.logic {
-            Logic::FixedContents(c) => {
-                let contents = c.iter().map(|i| quote! { #i });
-                return quote! { buf.ensure_fixed_contents(&[#(#contents),*])?; };
-            }
-            Logic::Type(ty) => ty.expr(endianness),
-            Logic::Switch { .. } => todo!(),
-            Logic::Size(size) => match size {
-                Size::Fixed(count) => quote! { buf.read_bytes(#count)? },
-                Size::Eos => quote! { buf.read_bytes_full()? },
-            },
-            Logic::Process(_) => todo!(),
-        };
+    /// If `is_async` is set, every read is awaited, e.g. `buf.read_u4le().await?`.
+    ///
+    /// In debug mode, the offsets the `Attribute` and its elements were read from are pushed to
+    /// `_debug`, a `::kaitai::DebugSpans`.
+    ///
+    /// If the type of the `Attribute` switches its endianness on this type, the endianness is
+    /// decided before reading it, see [`endian_statements`](Attribute::endian_statements). If
+    /// types switch their endianness on the `Attribute`, it is decided after reading it, see
+    /// [`endian_decision_statements`](Attribute::endian_decision_statements).
+    pub fn variable_assignment(&self, endianness: Endianness, is_async: bool) -> TokenStream {
+        let (span_start, span_end) = self.span_statements(is_async);
+        let (endian_start, endian_end) = self.endian_statements(false);
+        let assignment = self.variable_assignment_inner(endianness, is_async);
+        let decision = self.endian_decision_statements(false);
+        quote! {
+            #span_start
+            #endian_start
+            #assignment
+            #endian_end
+            #span_end
+            #decision
+        }
+    }
+
+    fn variable_assignment_inner(&self, endianness: Endianness, is_async: bool) -> TokenStream {
+        let field = self.field_statement(endianness);
+        if let Logic::FixedContents(c) = &self.logic {
+            let contents = self.fixed_contents_statement(c, is_async);
+            return quote! {
+                #field
+                #contents
+            };
+        }
+        let id = &self.id;
+        if self.lazy {
+            let expr = self.lazy_expr(endianness, false, is_async);
+            return quote! {
+                #field
+                let #id = #expr;
+            };
+        }
+        let aw = await_tokens(is_async);
+        let (element_start, element_end) = self.element_span_statements(is_async);
+        let mut expr = self.element_expr(endianness, false, is_async);
 
         if let Some(repeat) = &self.repeat {
             expr = match repeat {
@@ -218,138 +723,1109 @@ impl Attribute {
                     quote! {
                         {
                             let mut result = Vec::new();
-                            while !buf.is_eof()? {
+                            while !buf.is_eof()#aw? {
+                                parse_state.repeat(result.len() as u64 + 1)?;
+                                parse_state.index(result.len() as u64);
+                                #element_start
+                                result.push(#expr);
+                                #element_end
+                            }
+                            result
+                        }
+                    }
+                }
+                Repeat::Expr(count) => {
+                    quote! {
+                        {
+                            parse_state.repeat(#count as u64)?;
+                            let mut result = Vec::new();
+                            for _ in 0..#count {
+                                parse_state.index(result.len() as u64);
+                                #element_start
                                 result.push(#expr);
+                                #element_end
                             }
                             result
                         }
                     }
                 }
-                Repeat::Expr(_) => todo!(),
-                Repeat::Until(_) => todo!(),
+                Repeat::Until(_) => unsupported(id, "repeat: until"),
             }
         }
 
-        let id = &self.id;
-        quote! { let #id = #expr; }
+        quote! {
+            #field
+            let #id = #expr;
+        }
     }
-}
-
-impl TryFrom<(Option<de::meta::MetaDoc>, de::attr::Attr)> for Attribute {
-    type Error = ();
 
-    fn try_from(
-        (meta_doc, attr): (Option<de::meta::MetaDoc>, de::attr::Attr),
-    ) -> Result<Self, Self::Error> {
-        let id = Ident::new(&attr.id.unwrap(), Span::call_site());
-        let doc = (meta_doc, attr.doc).into();
-        let repeat = match attr.repeat {
-            Some(repeat) => Some(match repeat {
-                de::attr::Repeat::Eos => Repeat::Eos,
-                de::attr::Repeat::Expr => Repeat::Expr(attr.repeat_expr.unwrap()),
-                de::attr::Repeat::Until => Repeat::Until(attr.repeat_until.unwrap()),
-            }),
-            None => None,
-        };
-        let logic = {
-            if let Some(contents) = attr.contents {
-                Logic::FixedContents(contents)
-            } else if let Some(size) = attr.size {
-                Logic::Size(Size::Fixed(size))
-            } else if attr.size_eos {
-                Logic::Size(Size::Eos)
-            } else {
-                match attr.ty.unwrap() {
-                    de::attr::AttrType::TypeRef(type_ref) => {
-                        Logic::Type(Type::from((type_ref, attr.en)))
-                    }
-                    de::attr::AttrType::Switch {
-                        switch_on: on,
-                        cases,
-                    } => Logic::Switch {
-                        on,
-                        cases: cases.into_iter().map(|(_k, _v)| todo!()).collect(),
-                    },
-                }
+    /// Returns a [`TokenStream`] telling the `ParseState` that the `Attribute` is being read, so
+    /// that errors give the path to it.
+    fn field_statement(&self, endianness: Endianness) -> TokenStream {
+        let id = self.id.to_string();
+        let ty = match &self.logic {
+            Logic::FixedContents(_) => "contents".to_owned(),
+            Logic::Type(Type::UserDefined { id, .. }) => id.to_string(),
+            Logic::Type(Type::Opaque { path, .. }) => {
+                path.segments.last().unwrap().ident.to_string()
             }
+            Logic::Type(Type::BuiltIn { en: Some(en), .. }) => en.to_string(),
+            Logic::Type(Type::BuiltIn { ty, en: None }) => {
+                format!("{}{}", ty.ks_type(), ty.endianness(endianness))
+            }
+            Logic::Switch(_) => "switch".to_owned(),
+            Logic::Size(_) | Logic::Process(_) => "bytes".to_owned(),
+            Logic::Str(_) => "str".to_owned(),
         };
-
-        Ok(Self {
-            id,
-            doc,
-            repeat,
-            logic,
-        })
+        quote! { parse_state.field(#id, #ty); }
     }
-}
-
-#[derive(Clone, Debug)]
-pub enum Logic {
-    FixedContents(Vec<u8>),
-    Type(Type),
-    Switch {
-        on: String,
-        cases: Vec<(Pattern, Type)>,
-    },
-    // TODO: if logic
-    Size(Size),
-    // TODO: probably don't use string
-    Process(String),
-}
-
-// TODO: pad-right
-// TODO: pos
-// TODO: io
-// TODO: value
 
-#[derive(Clone, Debug)]
-pub enum Type {
-    UserDefined(Ident),
-    BuiltIn { ty: BuiltInType, en: Option<Ident> },
-}
+    /// Returns a [`TokenStream`] checking that the stream continues with `contents`.
+    fn fixed_contents_statement(&self, contents: &[u8], is_async: bool) -> TokenStream {
+        let aw = await_tokens(is_async);
+        let contents = contents.iter().map(|i| quote! { #i });
+        quote! { buf.ensure_fixed_contents(&[#(#contents),*])#aw?; }
+    }
 
-impl Type {
-    fn ty(&self) -> TokenStream {
-        match self {
-            Type::UserDefined(id) => id.into_token_stream(),
-            Type::BuiltIn { ty, en } => {
-                if let Some(enum_id) = en {
-                    enum_id.into_token_stream()
-                } else {
-                    ty.to_token_stream()
-                }
-            }
+    /// Returns the statements recording the offsets the `Attribute` is read from in `_debug`, the
+    /// first to go before reading it and the second after. Both are empty unless debug mode is
+    /// on.
+    fn span_statements(&self, is_async: bool) -> (TokenStream, TokenStream) {
+        if !self.options.ks_debug {
+            return Default::default();
+        }
+        let aw = await_tokens(is_async);
+        let name = self.id.to_string();
+        if self.repeat.is_some() && !self.lazy {
+            (
+                quote! {
+                    let span_start = buf.pos()#aw?;
+                    let mut element_spans = ::std::vec::Vec::new();
+                },
+                quote! { _debug.push(#name, span_start..buf.pos()#aw?, element_spans); },
+            )
+        } else {
+            (
+                quote! { let span_start = buf.pos()#aw?; },
+                quote! { _debug.push(#name, span_start..buf.pos()#aw?, ::std::vec::Vec::new()); },
+            )
         }
     }
 
-    fn expr(&self, endianness: Endianness) -> TokenStream {
-        match self {
-            Type::UserDefined(id) => quote! { <#id as ::kaitai::KaitaiStruct>::new(buf)? },
-            Type::BuiltIn { ty, en } => {
-                let read_call =
-                    format!("buf.read_{}{}()?", ty.ks_type(), ty.endianness(endianness))
-                        .parse()
-                        .unwrap();
-                if let Some(enum_ident) = en {
-                    quote! { #enum_ident::n(#read_call).ok_or(::kaitai::error::Error::NoEnumMatch)? }
-                } else {
-                    read_call
-                }
+    /// Returns the statements deciding the endianness of the type of the `Attribute`, if it
+    /// switches on an attribute of this type, the first to go before reading it and the second
+    /// after. The endianness is kept in the `ParseState` while the type is read, and the previous
+    /// endianness is restored afterwards. If `on_self` is set, the attribute switched on is
+    /// accessed through `self` rather than through a variable.
+    fn endian_statements(&self, on_self: bool) -> (TokenStream, TokenStream) {
+        match &self.endian {
+            Some(Ok(endian)) => {
+                let is_le = endian.expr(on_self);
+                (
+                    quote! { let outer_le = parse_state.set_le(#is_le); },
+                    quote! { parse_state.set_le(outer_le); },
+                )
             }
+            Some(Err(message)) => (
+                quote! { ::std::compile_error!(#message); },
+                TokenStream::new(),
+            ),
+            None => Default::default(),
         }
     }
-}
 
-// TODO: cow?
-impl From<(String, Option<String>)> for Type {
-    fn from((type_ref, en): (String, Option<String>)) -> Self {
-        if let Ok(built_in) = BuiltInType::try_from(type_ref.as_ref()) {
-            Type::BuiltIn {
-                ty: built_in,
-                en: en.map(|id| Ident::new(&sc_to_ucc(&id), Span::call_site())),
-            }
+    /// Returns the statements deciding the endianness of the types switching on the `Attribute`,
+    /// to go after reading it. The endianness of the owner of the `Attribute` is decided as
+    /// `is_le`, and kept in the `ParseState` until the owner is read, the `ParseState` restoring
+    /// the previous endianness when leaving it. The endianness of other types is kept in the
+    /// `ParseState` until they are read. If `on_self` is set, the `Attribute` is accessed through `self`
+    /// rather than through a variable.
+    ///
+    /// # Examples
+    ///
+    /// ```yaml
+    /// meta:
+    ///   endian:
+    ///     switch-on: indicator
+    ///     cases:
+    ///       '[0x49, 0x49]': le
+    ///       '[0x4d, 0x4d]': be
+    /// ```
+    /// results in
+    /// ```ignore
+    /// let is_le = (match &*indicator {
+    ///     [0x49, 0x49] => Some(true),
+    ///     [0x4d, 0x4d] => Some(false),
+    ///     _ => None,
+    /// }).ok_or(::kaitai::error::Error::UndecidedEndianness)?;
+    /// parse_state.set_le(Some(is_le));
+    /// ```
+    fn endian_decision_statements(&self, on_self: bool) -> TokenStream {
+        self.decides_endian
+            .iter()
+            .map(|(ty, endian)| {
+                let is_le = endian.expr(on_self);
+                match ty {
+                    Some(ty) => {
+                        let name = ty.to_string();
+                        quote! { parse_state.set_type_le(#name, #is_le); }
+                    }
+                    None => quote! {
+                        let is_le = (#is_le).ok_or(::kaitai::error::Error::UndecidedEndianness)?;
+                        parse_state.set_le(::std::option::Option::Some(is_le));
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the statements recording the offsets an element of the `Attribute` is read from in
+    /// `element_spans`, like [`span_statements`](Attribute::span_statements).
+    fn element_span_statements(&self, is_async: bool) -> (TokenStream, TokenStream) {
+        if !self.options.ks_debug {
+            return Default::default();
+        }
+        let aw = await_tokens(is_async);
+        (
+            quote! { let element_start = buf.pos()#aw?; },
+            quote! { element_spans.push(element_start..buf.pos()#aw?); },
+        )
+    }
+
+    /// Returns a [`TokenStream`] of an expression reading a single element of the `Attribute`,
+    /// i.e. the whole `Attribute` if it isn't repeated. If `on_self` is set, sizes are accessed
+    /// through `self` rather than through variables.
+    fn element_expr(&self, endianness: Endianness, on_self: bool, is_async: bool) -> TokenStream {
+        let mut expr = match &self.logic {
+            Logic::FixedContents(_) => unreachable!(),
+            Logic::Type(ty) => ty.expr(endianness, self.strict, is_async),
+            Logic::Switch(switch) => switch.expr(endianness, on_self, is_async),
+            Logic::Size(size) => self.bytes_expr(size, on_self, is_async),
+            Logic::Str(size) => {
+                let bytes = self.bytes_expr(size, on_self, is_async);
+                if self.options.borrowed {
+                    quote! { ::std::str::from_utf8(#bytes)? }
+                } else {
+                    quote! { ::std::string::String::from_utf8(#bytes).map_err(|e| e.utf8_error())? }
+                }
+            }
+            Logic::Process(_) => unsupported(&self.id, "process"),
+        };
+
+        if let Some(valid) = &self.valid {
+            let path = self.id.to_string();
+            let condition = valid.condition(&self.id, &quote! { &value });
+            let constraint = valid.to_string();
+            expr = quote! {
+                {
+                    let value = #expr;
+                    if !(#condition) {
+                        return Err(::kaitai::error::Error::ValidationFailed(
+                            ::kaitai::error::Violation::Invalid {
+                                path: #path.to_owned(),
+                                actual: format!("{:?}", value),
+                                constraint: #constraint.to_owned(),
+                            },
+                        ));
+                    }
+                    value
+                }
+            };
+        }
+
+        expr
+    }
+
+    /// Returns a [`TokenStream`] reading the `Attribute` into the existing field of `self`,
+    /// reusing the allocations of byte arrays, strings, repeated attributes and user defined
+    /// types.
+    ///
+    /// # Examples
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: example_type
+    /// repeat: eos
+    /// ```
+    /// results in
+    /// ```ignore
+    /// parse_state.field("example_attr", "ExampleType");
+    /// {
+    ///     let mut i = 0;
+    ///     while !buf.is_eof()? {
+    ///         parse_state.repeat(i as u64 + 1)?;
+    ///         parse_state.index(i as u64);
+    ///         if i < self.example_attr.len() {
+    ///             ::kaitai::KaitaiStruct::read_with(&mut self.example_attr[i], buf, parse_state)?;
+    ///         } else {
+    ///             self.example_attr.push(<ExampleType as ::kaitai::KaitaiStruct>::new_with(buf, parse_state)?);
+    ///         }
+    ///         i += 1;
+    ///     }
+    ///     self.example_attr.truncate(i);
+    /// }
+    /// ```
+    ///
+    /// In debug mode, the offsets are pushed to `_debug` like in
+    /// [`variable_assignment`](Attribute::variable_assignment).
+    pub fn read_statement(&self, endianness: Endianness) -> TokenStream {
+        if let Logic::FixedContents(_) = &self.logic {
+            return self.variable_assignment(endianness, false);
+        }
+        let (endian_start, endian_end) = self.endian_statements(true);
+        let read = self.read_statement_inner(endianness);
+        let decision = self.endian_decision_statements(true);
+        quote! {
+            #endian_start
+            #read
+            #endian_end
+            #decision
+        }
+    }
+
+    fn read_statement_inner(&self, endianness: Endianness) -> TokenStream {
+        let id = &self.id;
+        let field = self.field_statement(endianness);
+        let (span_start, span_end) = self.span_statements(false);
+        if self.lazy {
+            let expr = self.lazy_expr(endianness, true, false);
+            return quote! {
+                #span_start
+                #field
+                self.#id = #expr;
+                #span_end
+            };
+        }
+        let (element_start, element_end) = self.element_span_statements(false);
+        let read = match &self.repeat {
+            None => self.read_into(&quote! { self.#id }, endianness),
+            Some(repeat) => {
+                let (count_check, condition, element_check) = match repeat {
+                    Repeat::Eos => (
+                        TokenStream::new(),
+                        quote! { !buf.is_eof()? },
+                        quote! { parse_state.repeat(i as u64 + 1)?; },
+                    ),
+                    Repeat::Expr(count) => {
+                        let count = count.self_tokens();
+                        (
+                            quote! { parse_state.repeat(#count as u64)?; },
+                            quote! { i < #count as usize },
+                            TokenStream::new(),
+                        )
+                    }
+                    Repeat::Until(_) => {
+                        let unsupported = unsupported(id, "repeat: until");
+                        return quote! { #unsupported; };
+                    }
+                };
+                let read_into = self.read_into(&quote! { self.#id[i] }, endianness);
+                let expr = self.element_expr(endianness, true, false);
+                quote! {
+                    {
+                        #count_check
+                        let mut i = 0;
+                        while #condition {
+                            #element_check
+                            parse_state.index(i as u64);
+                            #element_start
+                            if i < self.#id.len() {
+                                #read_into
+                            } else {
+                                self.#id.push(#expr);
+                            }
+                            #element_end
+                            i += 1;
+                        }
+                        self.#id.truncate(i);
+                    }
+                }
+            }
+        };
+        quote! {
+            #span_start
+            #field
+            #read
+            #span_end
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading a single element of the `Attribute` into `place`.
+    fn read_into(&self, place: &TokenStream, endianness: Endianness) -> TokenStream {
+        match &self.logic {
+            Logic::Type(Type::UserDefined { .. } | Type::Opaque { .. }) => {
+                quote! { ::kaitai::KaitaiStruct::read_with(&mut #place, buf, parse_state)?; }
+            }
+            // Byte arrays and strings with a `valid` are read like other values, to be checked.
+            Logic::Size(size) if self.valid.is_none() => {
+                size.read_into_statement(&quote! { &mut #place })
+            }
+            Logic::Str(size) if self.valid.is_none() => {
+                let read_into = size.read_into_statement(&quote! { &mut bytes });
+                quote! {
+                    {
+                        let mut bytes = ::std::mem::take(&mut #place).into_bytes();
+                        #read_into
+                        #place = ::std::string::String::from_utf8(bytes).map_err(|e| e.utf8_error())?;
+                    }
+                }
+            }
+            _ => {
+                let expr = self.element_expr(endianness, true, false);
+                quote! { #place = #expr; }
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading the `Attribute` into the partially created struct
+    /// `partial_self`. The field is set as soon as the `Attribute` starts being read, so that when
+    /// an error is returned, it holds whatever was read before the error, e.g. the elements read
+    /// so far of a repeated attribute, or a partially created user defined type.
+    ///
+    /// # Examples
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: example_type
+    /// ```
+    /// results in
+    /// ```ignore
+    /// parse_state.field("example_attr", "ExampleType");
+    /// {
+    ///     let mut element = ::std::default::Default::default();
+    ///     let result = <ExampleType as ::kaitai::KaitaiStruct>::new_partial_with(buf, parse_state, &mut element);
+    ///     partial_self.example_attr = Some(element);
+    ///     result?;
+    /// }
+    /// ```
+    pub fn partial_statement(&self, endianness: Endianness) -> TokenStream {
+        let (endian_start, endian_end) = self.endian_statements(false);
+        let statement = self.partial_statement_inner(endianness);
+        let decision = self.endian_decision_statements(false);
+        quote! {
+            #endian_start
+            #statement
+            #endian_end
+            #decision
+        }
+    }
+
+    fn partial_statement_inner(&self, endianness: Endianness) -> TokenStream {
+        if let Logic::FixedContents(c) = &self.logic {
+            let field = self.field_statement(endianness);
+            let contents = self.fixed_contents_statement(c, false);
+            return quote! {
+                #field
+                #contents
+            };
+        }
+
+        let id = &self.id;
+        let field = self.field_statement(endianness);
+        if self.lazy {
+            let expr = self.lazy_expr(endianness, false, false);
+            return quote! {
+                #field
+                partial_self.#id = ::std::option::Option::Some(#expr);
+            };
+        }
+
+        let partial_element = |place: TokenStream| match &self.logic {
+            Logic::Type(ty @ (Type::UserDefined { .. } | Type::Opaque { .. })) => {
+                let ty = ty.path();
+                quote! {
+                    let mut element = ::std::default::Default::default();
+                    let result = <#ty as ::kaitai::KaitaiStruct>::new_partial_with(buf, parse_state, &mut element);
+                    #place;
+                    result?;
+                }
+            }
+            _ => {
+                let expr = self.element_expr(endianness, false, false);
+                quote! {
+                    let element = #expr;
+                    #place;
+                }
+            }
+        };
+
+        match &self.repeat {
+            None => {
+                let element = partial_element(quote! {
+                    partial_self.#id = ::std::option::Option::Some(element)
+                });
+                // Sizes, counts and switches may refer to earlier attributes as variables, so
+                // integers, enums, byte arrays and strings are assigned to a variable as well.
+                let variable = match &self.logic {
+                    Logic::Type(Type::BuiltIn { .. }) => {
+                        quote! { let #id = partial_self.#id.unwrap(); }
+                    }
+                    Logic::Size(_) | Logic::Str(_) => {
+                        quote! { let #id = partial_self.#id.as_deref().unwrap(); }
+                    }
+                    _ => TokenStream::new(),
+                };
+                quote! {
+                    #field
+                    {
+                        #element
+                    }
+                    #variable
+                }
+            }
+            Some(repeat) => {
+                let (count_check, condition, element_check) = match repeat {
+                    Repeat::Eos => (
+                        TokenStream::new(),
+                        quote! { !buf.is_eof()? },
+                        quote! { parse_state.repeat(elements.len() as u64 + 1)?; },
+                    ),
+                    Repeat::Expr(count) => (
+                        quote! { parse_state.repeat(#count as u64)?; },
+                        quote! { (elements.len() as u64) < #count as u64 },
+                        TokenStream::new(),
+                    ),
+                    Repeat::Until(_) => {
+                        let unsupported = unsupported(id, "repeat: until");
+                        return quote! { #unsupported; };
+                    }
+                };
+                let element = partial_element(quote! { elements.push(element) });
+                quote! {
+                    #field
+                    {
+                        #count_check
+                        let elements = partial_self.#id.insert(::std::vec::Vec::new());
+                        while #condition {
+                            #element_check
+                            parse_state.index(elements.len() as u64);
+                            #element
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] writing the `Attribute` out to the stream. This is the inverse
+    /// of [`variable_assignment`](Attribute::variable_assignment).
+    ///
+    /// # Examples
+    /// All the following examples assume the format is little endian.
+    ///
+    /// ## Built-in
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: u4
+    /// ```
+    /// results in
+    /// ```ignore
+    /// buf.write_u4le(*&self.example_attr)?;
+    /// ```
+    ///
+    /// ## Custom type
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: example_type
+    /// ```
+    /// results in
+    /// ```ignore
+    /// <ExampleType as ::kaitai::KaitaiWrite>::write_to(&self.example_attr, buf)?;
+    /// ```
+    ///
+    /// ## Enum
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: u4
+    /// enum: example_enum
+    /// ```
+    /// results in
+    /// ```ignore
+    /// buf.write_u4le((&self.example_attr).value() as u32)?;
+    /// ```
+    ///
+    /// ## Fixed Contents
+    ///
+    /// ```yaml
+    /// name: id
+    /// contents: glTF
+    /// ```
+    /// results is
+    /// ```ignore
+    /// buf.write_fixed_contents("glTF".as_bytes())?;
+    /// ```
+    ///
+    ///
+    /// Lazy attributes aren't written, as their elements aren't stored.
+    pub fn write_statement(&self, endianness: Endianness) -> TokenStream {
+        if self.lazy {
+            return TokenStream::new();
+        }
+        let id = &self.id;
+        let value = match self.repeat {
+            Some(_) => quote! { item },
+            None => quote! { &self.#id },
+        };
+
+        let statement = match &self.logic {
+            Logic::FixedContents(c) => {
+                let contents = c.iter().map(|i| quote! { #i });
+                return quote! { buf.write_fixed_contents(&[#(#contents),*])?; };
+            }
+            Logic::Type(ty) => ty.write_expr(endianness, &value),
+            Logic::Switch(switch) => switch.write_expr(endianness, &value),
+            Logic::Size(size) => size.write_expr(&value),
+            Logic::Str(size) => size.write_expr(&quote! { (#value).as_bytes() }),
+            Logic::Process(_) => unsupported(id, "process"),
+        };
+
+        // The elements of every kind of repeat are stored, including the one ending a
+        // `repeat: until`, so they are all written back in order.
+        match &self.repeat {
+            Some(_) => quote! {
+                for item in self.#id.iter() {
+                    #statement;
+                }
+            },
+            None => quote! { #statement; },
+        }
+    }
+
+    /// Returns a [`TokenStream`] pushing a `::kaitai::error::Violation` to
+    /// `violations` for every invariant of the `Attribute` that `self` doesn't uphold. The
+    /// attributes of user defined types are checked recursively.
+    ///
+    /// # Examples
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// size: len_example_attr
+    /// ```
+    /// results in
+    /// ```ignore
+    /// {
+    ///     let path = format!("{}{}", path, "example_attr");
+    ///     if (&self.example_attr).len() as u64 != self.len_example_attr as u64 {
+    ///         violations.push(::kaitai::error::Violation::Size { .. });
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// If the `Attribute` has no invariants, or is lazy, an empty [`TokenStream`] is returned.
+    pub fn check_statement(&self) -> TokenStream {
+        if self.lazy {
+            return TokenStream::new();
+        }
+        let id = &self.id;
+        let id_str = id.to_string();
+        let value = match self.repeat {
+            Some(_) => quote! { item },
+            None => quote! { &self.#id },
+        };
+
+        let mut checks = Vec::new();
+        match &self.logic {
+            Logic::Size(Size::Fixed(size)) | Logic::Str(Size::Fixed(size)) => {
+                let size = size.self_tokens();
+                checks.push(quote! {
+                    if (#value).len() as u64 != #size as u64 {
+                        violations.push(::kaitai::error::Violation::Size {
+                            path: path.clone(),
+                            expected: #size as u64,
+                            actual: (#value).len() as u64,
+                        });
+                    }
+                });
+            }
+            Logic::Size(Size::Truncated { size, .. })
+            | Logic::Str(Size::Truncated { size, .. }) => {
+                // Truncated bytes can be shorter than their size, but not longer.
+                if let Size::Fixed(size) = size.as_ref() {
+                    let size = size.self_tokens();
+                    checks.push(quote! {
+                        if (#value).len() as u64 > #size as u64 {
+                            violations.push(::kaitai::error::Violation::Size {
+                                path: path.clone(),
+                                expected: #size as u64,
+                                actual: (#value).len() as u64,
+                            });
+                        }
+                    });
+                }
+            }
+            Logic::Type(ty @ (Type::UserDefined { .. } | Type::Opaque { .. })) => {
+                let ty = ty.path();
+                checks.push(quote! {
+                    <#ty as ::kaitai::KaitaiWrite>::check_at(#value, &format!("{}.", path), violations);
+                })
+            }
+            Logic::Switch(switch) => checks.extend(switch.check_statement(&value)),
+            _ => {}
+        }
+        if let Some(valid) = &self.valid {
+            let condition = valid.condition(id, &value);
+            let constraint = valid.to_string();
+            checks.push(quote! {
+                if !(#condition) {
+                    violations.push(::kaitai::error::Violation::Invalid {
+                        path: path.clone(),
+                        actual: format!("{:?}", #value),
+                        constraint: #constraint.to_owned(),
+                    });
+                }
+            });
+        }
+
+        match &self.repeat {
+            Some(repeat) => {
+                let count_check = match repeat {
+                    Repeat::Expr(count) => {
+                        let count = count.self_tokens();
+                        quote! {
+                            if self.#id.len() as u64 != #count as u64 {
+                                violations.push(::kaitai::error::Violation::Count {
+                                    path: format!("{}{}", path, #id_str),
+                                    expected: #count as u64,
+                                    actual: self.#id.len() as u64,
+                                });
+                            }
+                        }
+                    }
+                    _ => TokenStream::new(),
+                };
+                if checks.is_empty() {
+                    return count_check;
+                }
+                quote! {
+                    #count_check
+                    for (i, item) in self.#id.iter().enumerate() {
+                        let path = format!("{}{}[{}]", path, #id_str, i);
+                        #(#checks)*
+                    }
+                }
+            }
+            None if checks.is_empty() => TokenStream::new(),
+            None => quote! {
+                {
+                    let path = format!("{}{}", path, #id_str);
+                    #(#checks)*
+                }
+            },
+        }
+    }
+
+    /// Returns a [`TokenStream`] passing the `Attribute` to `visitor`, a
+    /// `&mut dyn ::kaitai::visit::Visitor`.
+    ///
+    /// # Examples
+    ///
+    /// ```yaml
+    /// name: example_attr
+    /// type: u4
+    /// ```
+    /// results in
+    /// ```ignore
+    /// visitor.visit_field(::kaitai::visit::Field {
+    ///     name: "example_attr",
+    ///     doc: "",
+    ///     span: None,
+    ///     value: ::kaitai::visit::Value::Unsigned(*(&self.example_attr) as u64),
+    /// });
+    /// ```
+    ///
+    /// Fixed contents attributes aren't stored, so an empty [`TokenStream`] is returned for them.
+    pub fn visit_statement(&self) -> TokenStream {
+        let id = &self.id;
+        let name = id.to_string();
+        let doc = self.doc.text();
+        let element = |value: &TokenStream| match &self.logic {
+            Logic::FixedContents(_) => unreachable!(),
+            Logic::Type(ty) => ty.visit_expr(value),
+            Logic::Switch(switch) => switch.visit_expr(value),
+            Logic::Size(_) => quote! {
+                ::kaitai::visit::Value::Bytes(::std::convert::AsRef::<[u8]>::as_ref(#value))
+            },
+            Logic::Str(_) => quote! {
+                ::kaitai::visit::Value::Str(::std::convert::AsRef::<str>::as_ref(#value))
+            },
+            Logic::Process(_) => unsupported(id, "process"),
+        };
+
+        let value = if let Logic::FixedContents(_) = self.logic {
+            return TokenStream::new();
+        } else if self.lazy {
+            quote! {
+                ::kaitai::visit::Value::Lazy {
+                    start: self.#id.start(),
+                    count: self.#id.count(),
+                }
+            }
+        } else if self.repeat.is_some() {
+            let element = element(&quote! { item });
+            let span = if self.options.ks_debug {
+                quote! { self._debug.element(#name, i) }
+            } else {
+                quote! { None }
+            };
+            quote! {
+                ::kaitai::visit::Value::Array(
+                    self.#id
+                        .iter()
+                        .enumerate()
+                        .map(|(i, item)| ::kaitai::visit::Element { span: #span, value: #element })
+                        .collect(),
+                )
+            }
+        } else {
+            element(&quote! { &self.#id })
+        };
+        let span = if self.options.ks_debug {
+            quote! { self._debug.span(#name) }
+        } else {
+            quote! { None }
+        };
+
+        quote! {
+            visitor.visit_field(::kaitai::visit::Field {
+                name: #name,
+                doc: #doc,
+                span: #span,
+                value: #value,
+            });
+        }
+    }
+}
+
+impl TryFrom<(Option<de::meta::MetaDoc>, Options, &Ident, de::attr::Attr)> for Attribute {
+    type Error = ();
+
+    fn try_from(
+        (meta_doc, options, owner, attr): (
+            Option<de::meta::MetaDoc>,
+            Options,
+            &Ident,
+            de::attr::Attr,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let id = Ident::new(&attr.id.unwrap(), Span::call_site());
+        let doc = (meta_doc, attr.doc).into();
+        let repeat = match attr.repeat {
+            Some(repeat) => Some(match repeat {
+                de::attr::Repeat::Eos => Repeat::Eos,
+                de::attr::Repeat::Expr => Repeat::Expr(attr.repeat_expr.unwrap()),
+                de::attr::Repeat::Until => Repeat::Until(attr.repeat_until.unwrap()),
+            }),
+            None => None,
+        };
+        if let Some(encoding) = &attr.encoding {
+            check_encoding(encoding);
+        }
+        let str_type = match &attr.ty {
+            Some(de::attr::AttrType::TypeRef(type_ref)) if type_ref == "str" => Some(false),
+            Some(de::attr::AttrType::TypeRef(type_ref)) if type_ref == "strz" => Some(true),
+            _ => None,
+        };
+        let term = match (attr.terminator, str_type) {
+            (Some(term), _) => match u8::try_from(term) {
+                Ok(term) => Some(char::from(term)),
+                Err(_) => panic!("terminator {} of attribute {} isn't a byte", term, id),
+            },
+            (None, Some(true)) => Some('\0'),
+            (None, _) => None,
+        };
+        let size = if let Some(size) = attr.size {
+            Some(Size::Fixed(size))
+        } else if attr.size_eos {
+            Some(Size::Eos)
         } else {
-            Type::UserDefined(Ident::new(&sc_to_ucc(&type_ref), Span::call_site()))
+            None
+        };
+        let size = match (size, term) {
+            (Some(size), Some(term)) => Some(Size::Truncated {
+                size: Box::new(size),
+                term,
+                include: attr.include,
+            }),
+            (Some(size), None) => Some(size),
+            (None, Some(term)) => Some(Size::Term {
+                term,
+                include: attr.include,
+                consume: attr.consume,
+            }),
+            (None, None) => None,
+        };
+        let logic = {
+            if let Some(contents) = attr.contents {
+                Logic::FixedContents(contents)
+            } else if str_type.is_some() {
+                Logic::Str(size.expect("str attribute without a size or terminator"))
+            } else if let Some(size) = size {
+                Logic::Size(size)
+            } else {
+                match attr.ty.unwrap() {
+                    de::attr::AttrType::TypeRef(type_ref) => {
+                        Logic::Type(Type::from((type_ref, attr.en)))
+                    }
+                    de::attr::AttrType::Switch { switch_on, cases } => {
+                        Logic::Switch(Switch::new(owner, &id, &switch_on, cases))
+                    }
+                }
+            }
+        };
+
+        let valid = attr
+            .valid
+            .map(|valid| Valid::from(valid).with_logic(&logic));
+
+        let can_be_lazy = matches!(repeat, Some(Repeat::Eos | Repeat::Expr(_)))
+            && matches!(logic, Logic::Type(Type::UserDefined { .. }));
+        if attr.lazy && !can_be_lazy {
+            // TODO: lazy attributes of built-in types
+            panic!("lazy attribute {} isn't a repeated user defined type", id);
+        }
+        let lazy = attr.lazy || (options.lazy && can_be_lazy);
+
+        if attr.strict.is_some() && !matches!(logic, Logic::Type(Type::BuiltIn { en: Some(_), .. }))
+        {
+            panic!("strict attribute {} isn't an enum", id);
+        }
+        let strict = attr.strict.unwrap_or(options.strict_enums);
+
+        Ok(Self {
+            id,
+            doc,
+            repeat,
+            valid,
+            logic,
+            options,
+            lazy,
+            strict,
+            endian: None,
+            decides_endian: Vec::new(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Logic {
+    FixedContents(Vec<u8>),
+    Type(Type),
+    Switch(Switch),
+    // TODO: if logic
+    Size(Size),
+    Str(Size),
+    // TODO: probably don't use string
+    Process(String),
+}
+
+// TODO: pad-right
+// TODO: pos
+// TODO: io
+// TODO: value
+
+#[derive(Clone, Debug)]
+pub enum Type {
+    UserDefined {
+        id: Ident,
+        borrowed: bool,
+    },
+    /// A type that isn't defined in the ksy file, implemented by hand, see
+    /// [`set_opaque`](Attributes::set_opaque).
+    Opaque {
+        path: syn::Path,
+        /// Whether the type implements `Eq` and `Ord`, as given by the `opaque_types` option.
+        eq: bool,
+    },
+    BuiltIn {
+        ty: BuiltInType,
+        en: Option<Ident>,
+    },
+}
+
+impl Type {
+    /// Returns whether the type is a float, or a user defined type in `floats`, the names of the
+    /// types containing floats. Opaque types may contain floats, so they count as floats unless
+    /// they are said to implement `Eq` and `Ord`.
+    fn is_float(&self, floats: &HashSet<String>) -> bool {
+        match self {
+            Type::UserDefined { id, .. } => floats.contains(&id.to_string()),
+            Type::Opaque { eq, .. } => !eq,
+            Type::BuiltIn { ty, en: None } => ty.int_layout().is_none(),
+            Type::BuiltIn { .. } => false,
+        }
+    }
+
+    /// Returns whether the type is an opaque type, or a user defined type in `opaque`, the names
+    /// of the types containing opaque types.
+    fn is_opaque(&self, opaque: &HashSet<String>) -> bool {
+        match self {
+            Type::UserDefined { id, .. } => opaque.contains(&id.to_string()),
+            Type::Opaque { .. } => true,
+            Type::BuiltIn { .. } => false,
+        }
+    }
+
+    /// Replaces the type of the attribute `attr` with an opaque type if it is a user defined type
+    /// that isn't defined, see [`Attributes::set_opaque`].
+    fn set_opaque(
+        &mut self,
+        attr: &Ident,
+        enabled: bool,
+        defined: &HashSet<String>,
+        types: &HashMap<String, OpaqueType>,
+    ) {
+        if let Type::UserDefined { id, .. } = self {
+            let name = id.to_string();
+            if defined.contains(&name) {
+                return;
+            }
+            if !enabled {
+                panic!(
+                    "attribute {} has unknown type {}, set `ks-opaque-types: true` in the meta \
+                     section or pass the `opaque_types` option to use a type defined outside the \
+                     ksy file",
+                    attr, name
+                );
+            }
+            let ty = types.get(&name).cloned().unwrap_or_default();
+            *self = Type::Opaque {
+                path: ty.path.unwrap_or_else(|| id.clone().into()),
+                eq: ty.eq,
+            };
+        }
+    }
+
+    fn ty(&self) -> TokenStream {
+        match self {
+            Type::UserDefined { id, borrowed: true } => quote! { #id<'a> },
+            Type::UserDefined { id, .. } => id.into_token_stream(),
+            Type::Opaque { path, .. } => path.into_token_stream(),
+            Type::BuiltIn { ty, en } => {
+                if let Some(enum_id) = en {
+                    enum_id.into_token_stream()
+                } else {
+                    ty.to_token_stream()
+                }
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] of the type for use in expressions, where its lifetime (if any)
+    /// can be inferred.
+    fn path(&self) -> TokenStream {
+        match self {
+            Type::UserDefined { id, borrowed: true } => quote! { #id<'_> },
+            _ => self.ty(),
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading the type. If `strict` is set, enums fail on values
+    /// missing from the enum rather than reading them as `Unknown`.
+    fn expr(&self, endianness: Endianness, strict: bool, is_async: bool) -> TokenStream {
+        match self {
+            Type::UserDefined { id, borrowed: true } => {
+                quote! { <#id<'a> as ::kaitai::KaitaiBorrowedStruct<'a>>::new_with(buf, parse_state)? }
+            }
+            Type::UserDefined { id, .. } if is_async => {
+                quote! { #id::new_async_in(buf, parse_state).await? }
+            }
+            Type::UserDefined { id, .. } => {
+                quote! { <#id as ::kaitai::KaitaiStruct>::new_with(buf, parse_state)? }
+            }
+            Type::Opaque { path, .. } => {
+                quote! { <#path as ::kaitai::KaitaiStruct>::new_with(buf, parse_state)? }
+            }
+            Type::BuiltIn { ty, en } => {
+                let aw = await_tokens(is_async);
+                let read_call = ty.with_endianness(endianness, |suffix| {
+                    let read_fn = format_ident!("read_{}", suffix);
+                    quote! { buf.#read_fn()#aw? }
+                });
+                match en {
+                    Some(enum_ident) if strict => {
+                        quote! { #enum_ident::n(#read_call).ok_or(::kaitai::error::Error::NoEnumMatch)? }
+                    }
+                    Some(enum_ident) => quote! { #enum_ident::from_value(#read_call) },
+                    None => read_call,
+                }
+            }
+        }
+    }
+
+    /// The inverse of [`expr`](Type::expr), writing the value behind the reference `value`.
+    fn write_expr(&self, endianness: Endianness, value: &TokenStream) -> TokenStream {
+        match self {
+            Type::UserDefined { .. } | Type::Opaque { .. } => {
+                let ty = self.path();
+                quote! { <#ty as ::kaitai::KaitaiWrite>::write_to(#value, buf)? }
+            }
+            Type::BuiltIn { ty, en } => {
+                let value = if en.is_some() {
+                    quote! { (#value).value() as #ty }
+                } else {
+                    quote! { *#value }
+                };
+                ty.with_endianness(endianness, |suffix| {
+                    let write_fn = format_ident!("write_{}", suffix);
+                    quote! { buf.#write_fn(#value)? }
+                })
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] of the `::kaitai::visit::Value` of the type behind the reference
+    /// `value`.
+    fn visit_expr(&self, value: &TokenStream) -> TokenStream {
+        match self {
+            Type::UserDefined { .. } => quote! { ::kaitai::visit::Value::Struct(#value) },
+            Type::Opaque { .. } => quote! { ::kaitai::visit::Value::Opaque(#value) },
+            Type::BuiltIn { en: Some(_), .. } => quote! {
+                ::kaitai::visit::Value::Enum {
+                    name: (#value).name(),
+                    value: (#value).value() as i128,
+                }
+            },
+            Type::BuiltIn { ty, en: None } => match ty.int_layout() {
+                Some((true, _)) => quote! { ::kaitai::visit::Value::Signed(*#value as i64) },
+                Some((false, _)) => quote! { ::kaitai::visit::Value::Unsigned(*#value as u64) },
+                None => quote! { ::kaitai::visit::Value::Float(*#value as f64) },
+            },
+        }
+    }
+}
+
+/// Returns a [`TokenStream`] failing compilation with an error saying that `feature`, used by the
+/// attribute `id`, isn't supported yet. It can be used as an expression, a statement or a type.
+fn unsupported(id: &Ident, feature: &str) -> TokenStream {
+    let message = format!("{} isn't supported yet, used by attribute {}", feature, id);
+    quote! { ::std::compile_error!(#message) }
+}
+
+/// Returns the tokens awaiting a read in async code, i.e. `.await`, or nothing.
+fn await_tokens(is_async: bool) -> TokenStream {
+    if is_async {
+        quote! { .await }
+    } else {
+        TokenStream::new()
+    }
+}
+
+// TODO: cow?
+impl From<(String, Option<String>)> for Type {
+    fn from((type_ref, en): (String, Option<String>)) -> Self {
+        if let Ok(built_in) = BuiltInType::try_from(type_ref.as_ref()) {
+            Type::BuiltIn {
+                ty: built_in,
+                en: en.map(|id| Ident::new(&sc_to_ucc(&id), Span::call_site())),
+            }
+        } else {
+            Type::UserDefined {
+                id: Ident::new(&sc_to_ucc(&type_ref), Span::call_site()),
+                borrowed: false,
+            }
         }
     }
 }
@@ -404,17 +1880,80 @@ impl BuiltInType {
         }
     }
 
+    /// Returns the integer type with the given signedness and number of bits.
+    pub fn integer(signed: bool, bits: u32) -> Self {
+        match (signed, bits) {
+            (false, 8) => BuiltInType::U8,
+            (false, 16) => BuiltInType::U16,
+            (false, 32) => BuiltInType::U32,
+            (false, 64) => BuiltInType::U64,
+            (true, 8) => BuiltInType::I8,
+            (true, 16) => BuiltInType::I16,
+            (true, 32) => BuiltInType::I32,
+            (true, 64) => BuiltInType::I64,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns whether the type is a signed integer, and its number of bits, or `None` if it is a
+    /// float.
+    pub fn int_layout(&self) -> Option<(bool, u32)> {
+        Some(match self {
+            BuiltInType::U8 => (false, 8),
+            BuiltInType::U16 => (false, 16),
+            BuiltInType::U32 => (false, 32),
+            BuiltInType::U64 => (false, 64),
+            BuiltInType::I8 => (true, 8),
+            BuiltInType::I16 => (true, 16),
+            BuiltInType::I32 => (true, 32),
+            BuiltInType::I64 => (true, 64),
+            BuiltInType::F32 | BuiltInType::F64 => return None,
+        })
+    }
+
     /// Returns a [`String`] describing the endianness of the `VariableContents`.
     ///
     /// Little-endian contents return "le". Big-endian contents return "be".
     ///
-    /// If the contents are of KS type `u1` or `s1`, the function will return an empty string.
+    /// If the contents are of KS type `u1` or `s1`, or their endianness is decided at runtime or
+    /// unspecified, the function will return an empty string.
     fn endianness(&self, endianness: Endianness) -> &'static str {
-        match &self {
-            BuiltInType::U8 | BuiltInType::I8 => "",
+        match (&self, endianness) {
+            (BuiltInType::U8 | BuiltInType::I8, _)
+            | (_, Endianness::Runtime | Endianness::Unspecified) => "",
             _ => endianness.into(),
         }
     }
+
+    /// Returns the [`TokenStream`] given by `call` for the suffix of the functions reading and
+    /// writing the type, e.g. `u4le`. If the endianness is decided at runtime, both the little and
+    /// big endian calls are made, depending on `is_le`.
+    fn with_endianness(
+        &self,
+        endianness: Endianness,
+        call: impl Fn(String) -> TokenStream,
+    ) -> TokenStream {
+        match (self, endianness) {
+            (BuiltInType::U8 | BuiltInType::I8, _) | (_, Endianness::Le | Endianness::Be) => {
+                call(format!("{}{}", self.ks_type(), self.endianness(endianness)))
+            }
+            (_, Endianness::Runtime) => {
+                let le = call(format!("{}le", self.ks_type()));
+                let be = call(format!("{}be", self.ks_type()));
+                quote! {
+                    if is_le {
+                        #le
+                    } else {
+                        #be
+                    }
+                }
+            }
+            (_, Endianness::Unspecified) => panic!(
+                "type {} needs an endianness, set `endian` in the meta section",
+                self.ks_type()
+            ),
+        }
+    }
 }
 
 impl ToTokens for BuiltInType {
@@ -437,16 +1976,564 @@ impl ToTokens for BuiltInType {
 // TODO: Encoding field on String type
 // TODO: terminator for String or Byte array
 
+/// An attribute whose type depends on the value of an earlier attribute. Its value is held in an
+/// enum generated for it, with a variant for each type.
 #[derive(Clone, Debug)]
+pub struct Switch {
+    /// The enum holding the value of the attribute.
+    ident: Ident,
+    /// The attribute the type depends on.
+    on: Ident,
+    /// The variants of the enum, one for each type, named after the type.
+    variants: Vec<(Ident, Type)>,
+    /// The cases of the switch, in the order they are written, and the index of their variant.
+    cases: Vec<(Pattern, usize)>,
+    /// Whether the enum can derive `Eq` and `Ord`, i.e. none of the types are or contain floats.
+    eq: bool,
+    /// The message failing compilation if a case doesn't fit in the type switched on.
+    error: Option<String>,
+}
+
+impl Switch {
+    /// Creates the switch of the attribute `id` of the type `owner`, from its `switch-on` and
+    /// `cases` keys.
+    fn new(owner: &Ident, id: &Ident, on: &str, cases: Vec<(String, String)>) -> Self {
+        // TODO: switch on expressions
+        if on.is_empty()
+            || on.starts_with(|c: char| c.is_ascii_digit())
+            || !on.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            panic!("switch-on `{}` of attribute {} isn't an attribute", on, id);
+        }
+
+        let mut variants: Vec<(Ident, Type)> = Vec::new();
+        let cases = cases
+            .into_iter()
+            .map(|(key, type_ref)| {
+                // TODO: strings and parameterized types
+                if type_ref == "str" || type_ref == "strz" || type_ref.contains('(') {
+                    panic!("unsupported case type `{}` in attribute {}", type_ref, id);
+                }
+                let ty = Type::from((type_ref, None));
+                let variant = match &ty {
+                    Type::UserDefined { id, .. } => id.clone(),
+                    Type::Opaque { .. } => unreachable!(),
+                    Type::BuiltIn { ty, .. } => {
+                        Ident::new(&sc_to_ucc(ty.ks_type()), Span::call_site())
+                    }
+                };
+                let index = match variants.iter().position(|(v, _)| *v == variant) {
+                    Some(index) => index,
+                    None => {
+                        variants.push((variant, ty));
+                        variants.len() - 1
+                    }
+                };
+                (Pattern::from(key.as_str()), index)
+            })
+            .collect();
+
+        Self {
+            ident: quote::format_ident!("{}{}", owner, sc_to_ucc(id.to_string())),
+            on: Ident::new(on, Span::call_site()),
+            variants,
+            cases,
+            eq: true,
+            error: None,
+        }
+    }
+
+    /// Checks that the integer cases of the switch of the attribute `id` fit in `ty`, the type of
+    /// the attribute switched on. If not, reading the attribute fails compilation.
+    fn check_int_cases(&mut self, id: &Ident, ty: &BuiltInType) {
+        let (min, max) = match ty.int_layout() {
+            Some((true, bits)) => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+            Some((false, bits)) => (0, (1i128 << bits) - 1),
+            None => return,
+        };
+        let out_of_range: Vec<_> = self
+            .cases
+            .iter()
+            .filter_map(|(p, _)| match p {
+                Pattern::Int(value) if *value < min || *value > max => Some(format!("`{}`", p)),
+                _ => None,
+            })
+            .collect();
+        if !out_of_range.is_empty() {
+            self.error = Some(format!(
+                "case {} doesn't fit in {}, the type of {}, used by attribute {}",
+                out_of_range.join(", "),
+                ty.ks_type(),
+                self.on,
+                id
+            ));
+        }
+    }
+
+    fn has_floats(&self, floats: &HashSet<String>) -> bool {
+        self.variants.iter().any(|(_, ty)| ty.is_float(floats))
+    }
+
+    /// Returns whether any of the types of the switch borrow from the bytes being parsed.
+    fn borrows(&self, borrowing: &HashSet<String>) -> bool {
+        self.variants.iter().any(|(_, ty)| match ty {
+            Type::UserDefined { id, .. } => borrowing.contains(&id.to_string()),
+            Type::Opaque { .. } | Type::BuiltIn { .. } => false,
+        })
+    }
+
+    fn set_borrowing(&mut self, borrowing: &HashSet<String>) {
+        for (_, ty) in self.variants.iter_mut() {
+            if let Type::UserDefined { id, borrowed } = ty {
+                *borrowed = borrowing.contains(&id.to_string());
+            }
+        }
+    }
+
+    fn is_borrowed(&self) -> bool {
+        self.variants
+            .iter()
+            .any(|(_, ty)| matches!(ty, Type::UserDefined { borrowed: true, .. }))
+    }
+
+    /// Returns the ids of the variants of the enums switched on that have no case, or nothing if
+    /// the switch has a default case. Panics if a case names a missing enum or variant.
+    fn missing_variants(&self, enums: &HashMap<String, Vec<String>>) -> Vec<String> {
+        let mut missing = Vec::new();
+        let has_default = self
+            .cases
+            .iter()
+            .any(|(p, _)| matches!(p, Pattern::Default));
+        for (pattern, _) in self.cases.iter() {
+            if let Pattern::Enum { en, variant } = pattern {
+                let variants = enums
+                    .get(&sc_to_ucc(en))
+                    .unwrap_or_else(|| panic!("case `{}::{}` has no enum {}", en, variant, en));
+                if !variants.contains(variant) {
+                    panic!("case `{}::{}` isn't a variant of enum {}", en, variant, en);
+                }
+                if has_default {
+                    continue;
+                }
+                for v in variants {
+                    let covered = self.cases.iter().any(|(p, _)| {
+                        matches!(p, Pattern::Enum { en: e, variant } if e == en && variant == v)
+                    });
+                    let name = format!("{}::{}", en, v);
+                    if !covered && !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+            }
+        }
+        missing
+    }
+
+    /// Returns a [`TokenStream`] of the type of the enum holding the value of the switch.
+    fn ty(&self) -> TokenStream {
+        let ident = &self.ident;
+        if self.is_borrowed() {
+            quote! { #ident<'a> }
+        } else {
+            ident.into_token_stream()
+        }
+    }
+
+    /// Returns the definition of the enum holding the value of the switch of the attribute `id`.
+    fn definition(&self, id: &Ident, user_derives: &[syn::Path]) -> TokenStream {
+        let ty = self.ty();
+        let derive = derive_attribute(struct_derives(self.eq), user_derives);
+        let serde = serde_attributes(!self.is_borrowed());
+        let on = self.on.to_string();
+        let variant_doc = |variant: &Ident| {
+            let keys = self
+                .cases
+                .iter()
+                .filter(|(_, index)| self.variants[*index].0 == *variant)
+                .map(|(pattern, _)| format!("`{}`", pattern))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(" The value of the attribute when `{}` is {}.", on, keys)
+        };
+        let variant_defs = self.variants.iter().map(|(variant, ty)| {
+            let doc = variant_doc(variant);
+            let ty = ty.ty();
+            quote! {
+                #[doc = #doc]
+                #variant(#ty)
+            }
+        });
+        let enum_doc = format!(" The value of [`{}`], whose type depends on `{}`.", id, on);
+        quote! {
+            #[doc = #enum_doc]
+            #derive
+            #serde
+            pub enum #ty {
+                #(#variant_defs),*
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading the variant of the case matching the attribute switched
+    /// on. If `on_self` is set, it is accessed through `self` rather than through a variable.
+    fn expr(&self, endianness: Endianness, on_self: bool, is_async: bool) -> TokenStream {
+        if let Some(message) = &self.error {
+            return quote! { ::std::compile_error!(#message) };
+        }
+        let ident = &self.ident;
+        let on = &self.on;
+        let mut on = if on_self {
+            quote! { self.#on }
+        } else {
+            on.into_token_stream()
+        };
+        // Strings and byte arrays are matched as slices.
+        if self
+            .cases
+            .iter()
+            .any(|(p, _)| matches!(p, Pattern::Str(_) | Pattern::Bytes(_)))
+        {
+            on = quote! { &*#on };
+        }
+
+        let arm = |pattern: &Pattern, index: usize| {
+            let (variant, ty) = &self.variants[index];
+            let expr = ty.expr(endianness, false, is_async);
+            quote! { #pattern => #ident::#variant(#expr) }
+        };
+        let arms = self
+            .cases
+            .iter()
+            .filter(|(p, _)| !matches!(p, Pattern::Default))
+            .map(|(p, index)| arm(p, *index));
+        let default_arm = match self
+            .cases
+            .iter()
+            .find(|(p, _)| matches!(p, Pattern::Default))
+        {
+            Some((p, index)) => arm(p, *index),
+            None => quote! { _ => return Err(::kaitai::error::Error::NoSwitchMatch) },
+        };
+        quote! {
+            match #on {
+                #(#arms,)*
+                #[allow(unreachable_patterns)]
+                #default_arm,
+            }
+        }
+    }
+
+    /// The inverse of [`expr`](Switch::expr), writing the variant behind the reference `value`.
+    fn write_expr(&self, endianness: Endianness, value: &TokenStream) -> TokenStream {
+        let ident = &self.ident;
+        let arms = self.variants.iter().map(|(variant, ty)| {
+            let write = ty.write_expr(endianness, &quote! { value });
+            quote! { #ident::#variant(value) => #write }
+        });
+        quote! {
+            match #value {
+                #(#arms),*
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] of the `::kaitai::visit::Value` of the variant behind the
+    /// reference `value`.
+    fn visit_expr(&self, value: &TokenStream) -> TokenStream {
+        let ident = &self.ident;
+        let arms = self.variants.iter().map(|(variant, ty)| {
+            let visit = ty.visit_expr(&quote! { value });
+            quote! { #ident::#variant(value) => #visit }
+        });
+        quote! {
+            match #value {
+                #(#arms),*
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] checking the user defined type of the variant behind the
+    /// reference `value`, if it has one.
+    fn check_statement(&self, value: &TokenStream) -> Option<TokenStream> {
+        let ident = &self.ident;
+        let arms: Vec<_> = self
+            .variants
+            .iter()
+            .filter(|(_, ty)| matches!(ty, Type::UserDefined { .. } | Type::Opaque { .. }))
+            .map(|(variant, ty)| {
+                let ty = ty.path();
+                quote! {
+                    #ident::#variant(value) => {
+                        <#ty as ::kaitai::KaitaiWrite>::check_at(value, &format!("{}.", path), violations);
+                    }
+                }
+            })
+            .collect();
+        if arms.is_empty() {
+            return None;
+        }
+        Some(quote! {
+            #[allow(unreachable_patterns)]
+            match #value {
+                #(#arms)*
+                _ => {}
+            }
+        })
+    }
+}
+
+/// The key of a case of a [`Switch`].
+#[derive(Clone, Debug, PartialEq)]
 pub enum Pattern {
-    Enum(String),
-    Int(u64),
+    /// A variant of an enum, given by the ids of the enum and the variant in the KSY file.
+    Enum {
+        en: String,
+        variant: String,
+    },
+    Int(i128),
+    Bool(bool),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// The `_` case, matching any value not matched by another case.
+    Default,
+}
+
+impl From<&str> for Pattern {
+    /// Parses the key of a case, see [`Pattern::parse`].
+    fn from(key: &str) -> Self {
+        Pattern::parse(key).unwrap_or_else(|e| panic!("{} in case `{}`", e, key.trim()))
+    }
+}
+
+impl Pattern {
+    /// Parses a literal of the KSY file, i.e. the key of a case or a `valid` value. Enum variants
+    /// are written as `enum::variant`, strings are quoted, and byte arrays are written as
+    /// `[0x50, 0x4b]`. Anything else, e.g. an expression, returns an error.
+    pub fn parse(key: &str) -> Result<Self, String> {
+        let key = key.trim();
+        match key {
+            "_" => return Ok(Pattern::Default),
+            "true" => return Ok(Pattern::Bool(true)),
+            "false" => return Ok(Pattern::Bool(false)),
+            _ => {}
+        }
+        for quote in ['"', '\''] {
+            if let Some(s) = key
+                .strip_prefix(quote)
+                .and_then(|key| key.strip_suffix(quote))
+            {
+                return Ok(Pattern::Str(s.to_owned()));
+            }
+        }
+        if let Some(bytes) = key.strip_prefix('[').and_then(|key| key.strip_suffix(']')) {
+            return bytes
+                .split(',')
+                .map(str::trim)
+                .filter(|b| !b.is_empty())
+                .map(|b| {
+                    parse_int(b)
+                        .and_then(|b| u8::try_from(b).ok())
+                        .ok_or_else(|| format!("invalid byte `{}`", b))
+                })
+                .collect::<Result<_, _>>()
+                .map(Pattern::Bytes);
+        }
+        if let Some((path, variant)) = key.rsplit_once("::") {
+            // The enum may be given by its path, e.g. `type::enum::variant`.
+            let en = path.rsplit("::").next().unwrap();
+            return Ok(Pattern::Enum {
+                en: en.to_owned(),
+                variant: variant.to_owned(),
+            });
+        }
+        parse_int(key)
+            .map(Pattern::Int)
+            .ok_or_else(|| "invalid literal".to_owned())
+    }
+}
+
+/// Parses an integer literal of a KSY file, which may be negative, and in hexadecimal, octal or
+/// binary.
+fn parse_int(s: &str) -> Option<i128> {
+    let s = s.replace('_', "");
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.as_str()),
+    };
+    let (radix, digits) = if let Some(digits) = s.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = s.strip_prefix("0o") {
+        (8, digits)
+    } else if let Some(digits) = s.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, s)
+    };
+    let value = i128::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Enum { en, variant } => write!(f, "{}::{}", en, variant),
+            Pattern::Int(value) => write!(f, "{}", value),
+            Pattern::Bool(value) => write!(f, "{}", value),
+            Pattern::Str(value) => write!(f, "{:?}", value),
+            Pattern::Bytes(bytes) => write!(f, "{:?}", bytes),
+            Pattern::Default => write!(f, "_"),
+        }
+    }
+}
+
+impl ToTokens for Pattern {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Pattern::Enum { en, variant } => {
+                let en = Ident::new(&sc_to_ucc(en), Span::call_site());
+                let variant = Ident::new(&sc_to_ucc(variant), Span::call_site());
+                quote! { #en::#variant }
+            }
+            Pattern::Int(value) => {
+                proc_macro2::Literal::i128_unsuffixed(*value).into_token_stream()
+            }
+            Pattern::Bool(value) => quote! { #value },
+            Pattern::Str(value) => proc_macro2::Literal::string(value).into_token_stream(),
+            Pattern::Bytes(bytes) => {
+                let bytes = bytes
+                    .iter()
+                    .map(|b| proc_macro2::Literal::u8_unsuffixed(*b));
+                quote! { [#(#bytes),*] }
+            }
+            Pattern::Default => quote! { _ },
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum Size {
     Fixed(IntegerValue),
     Eos,
+    Term {
+        term: char,
+        include: bool,
+        consume: bool,
+    },
+    /// A fixed size or the rest of the stream, whose bytes are truncated at the terminator, e.g.
+    /// a null padded `strz` with a `size`.
+    Truncated {
+        size: Box<Size>,
+        term: char,
+        include: bool,
+    },
+}
+
+impl Size {
+    /// Returns a [`TokenStream`] of the `TerminatorFlags` of a terminated size.
+    fn flags(&self) -> TokenStream {
+        let (include, consume) = match self {
+            Size::Term {
+                include, consume, ..
+            } => (include, consume),
+            // The bytes after the terminator are read anyway.
+            Size::Truncated { include, .. } => (include, &true),
+            _ => unreachable!(),
+        };
+        quote! {
+            ::kaitai::__private::TerminatorFlags {
+                include: #include,
+                consume: #consume,
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] reading into the vector behind the mutable reference `bytes`.
+    /// Sizes are accessed through `self`.
+    fn read_into_statement(&self, bytes: &TokenStream) -> TokenStream {
+        match self {
+            Size::Fixed(count) => {
+                let count = count.self_tokens();
+                quote! {
+                    parse_state.alloc(#count as u64)?;
+                    buf.read_bytes_into(#bytes, #count as usize)?;
+                }
+            }
+            Size::Eos => {
+                let alloc_check = Size::eos_alloc_check(false);
+                quote! {
+                    #alloc_check
+                    buf.read_bytes_full_into(#bytes)?;
+                }
+            }
+            Size::Term { term, .. } => {
+                let flags = self.flags();
+                quote! {
+                    buf.read_bytes_term_into(#bytes, #term, #flags)?;
+                    parse_state.alloc((#bytes).len() as u64)?;
+                }
+            }
+            Size::Truncated { size, term, .. } => {
+                let read_into = size.read_into_statement(bytes);
+                let flags = self.flags();
+                quote! {
+                    #read_into
+                    {
+                        let len = (#flags).truncate(#bytes, #term).len();
+                        (#bytes).truncate(len);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] accounting for the bytes remaining in the stream, before they are
+    /// read. The size of the stream is only queried if allocations are limited, as it can be
+    /// expensive.
+    fn eos_alloc_check(is_async: bool) -> TokenStream {
+        let aw = await_tokens(is_async);
+        quote! {
+            if parse_state.limits_alloc() {
+                parse_state.alloc(buf.size()#aw?.saturating_sub(buf.pos()#aw?))?;
+            }
+        }
+    }
+
+    /// Returns a [`TokenStream`] writing the bytes behind the reference `bytes`. Sizes are
+    /// accessed through `self`.
+    fn write_expr(&self, bytes: &TokenStream) -> TokenStream {
+        match self {
+            Size::Term { term, .. } => {
+                let flags = self.flags();
+                quote! { buf.write_bytes_term(#bytes, #term, #flags)? }
+            }
+            // Truncated bytes of a fixed size are padded with terminators, as they are usually
+            // null padded.
+            Size::Truncated { size, term, .. } => match size.as_ref() {
+                Size::Fixed(count) => {
+                    let count = count.self_tokens();
+                    quote! {
+                        {
+                            let bytes: &[u8] = #bytes;
+                            buf.write_bytes(bytes)?;
+                            for _ in bytes.len()..#count as usize {
+                                buf.write_u1(#term as u8)?;
+                            }
+                        }
+                    }
+                }
+                _ => quote! { buf.write_bytes(#bytes)? },
+            },
+            _ => quote! { buf.write_bytes(#bytes)? },
+        }
+    }
+}
+
+/// Panics if strings in `encoding` can't be read as UTF-8.
+pub fn check_encoding(encoding: &str) {
+    // TODO: other encodings
+    if !["utf-8", "utf8", "ascii"].contains(&encoding.to_lowercase().as_str()) {
+        panic!("unsupported encoding `{}`", encoding);
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -456,6 +2543,136 @@ pub enum Repeat {
     Until(String),
 }
 
+#[derive(Clone, Debug)]
+pub enum Valid {
+    Eq(Pattern),
+    Range {
+        min: Option<i128>,
+        max: Option<i128>,
+    },
+    AnyOf(Vec<Pattern>),
+    /// A constraint that can't be checked yet, e.g. an `expr`, described for the error.
+    Unsupported(String),
+}
+
+impl From<de::attr::Valid> for Valid {
+    fn from(valid: de::attr::Valid) -> Self {
+        let literal = |Scalar(value): Scalar| match Pattern::parse(&value) {
+            Ok(Pattern::Default) | Err(_) => Err(format!("valid value `{}`", value)),
+            Ok(pattern) => Ok(pattern),
+        };
+        let int = |Scalar(value): Scalar| {
+            parse_int(&value).ok_or_else(|| format!("valid bound `{}`", value))
+        };
+        let valid = match valid {
+            de::attr::Valid::Eq(value)
+            | de::attr::Valid::Constraints {
+                eq: Some(value), ..
+            } => literal(value).map(Valid::Eq),
+            de::attr::Valid::Constraints {
+                any_of: Some(values),
+                ..
+            } => values
+                .into_iter()
+                .map(literal)
+                .collect::<Result<_, _>>()
+                .map(Valid::AnyOf),
+            // TODO: expressions
+            de::attr::Valid::Constraints { expr: Some(_), .. } => Err("valid expr".to_owned()),
+            de::attr::Valid::Constraints { min, max, .. } => {
+                min.map(int).transpose().and_then(|min| {
+                    Ok(Valid::Range {
+                        min,
+                        max: max.map(int).transpose()?,
+                    })
+                })
+            }
+        };
+        valid.unwrap_or_else(Valid::Unsupported)
+    }
+}
+
+impl Valid {
+    /// Returns the constraint if it can be checked on the values of an attribute with `logic`,
+    /// i.e. integers are compared to integers, enums to their variants, byte arrays to byte
+    /// arrays and strings to strings. Otherwise, the constraint is unsupported.
+    fn with_logic(self, logic: &Logic) -> Self {
+        let is_int = matches!(
+            logic,
+            Logic::Type(Type::BuiltIn { ty, en: None })
+                if !matches!(ty, BuiltInType::F32 | BuiltInType::F64)
+        );
+        let fits = |pattern: &Pattern| match pattern {
+            Pattern::Int(_) => is_int,
+            Pattern::Enum { .. } => matches!(logic, Logic::Type(Type::BuiltIn { en: Some(_), .. })),
+            Pattern::Bytes(_) => matches!(logic, Logic::Size(_)),
+            Pattern::Str(_) => matches!(logic, Logic::Str(_)),
+            Pattern::Bool(_) | Pattern::Default => false,
+        };
+        let supported = match &self {
+            Valid::Eq(pattern) => fits(pattern),
+            Valid::Range { .. } => is_int,
+            Valid::AnyOf(patterns) => patterns.iter().all(fits),
+            Valid::Unsupported(_) => true,
+        };
+        if supported {
+            self
+        } else {
+            Valid::Unsupported(format!("valid `{}` on this type", self))
+        }
+    }
+
+    /// Returns a [`TokenStream`] of a boolean expression that is true if the value behind the
+    /// reference `value` satisfies the constraint. Unsupported constraints fail compilation,
+    /// naming the attribute `id`.
+    fn condition(&self, id: &Ident, value: &TokenStream) -> TokenStream {
+        let eq = |pattern: &Pattern| match pattern {
+            Pattern::Str(_) => quote! { ::std::convert::AsRef::<str>::as_ref(#value) == #pattern },
+            Pattern::Bytes(_) => {
+                quote! { ::std::convert::AsRef::<[u8]>::as_ref(#value) == &#pattern }
+            }
+            _ => quote! { ::std::cmp::PartialEq::eq(#value, &#pattern) },
+        };
+        match self {
+            Valid::Eq(pattern) => eq(pattern),
+            Valid::Range { min, max } => {
+                let literal = |v: &i128| proc_macro2::Literal::i128_unsuffixed(*v);
+                let min = min.iter().map(literal);
+                let max = max.iter().map(literal);
+                quote! {
+                    true
+                    #(&& ::std::cmp::PartialOrd::ge(#value, &#min))*
+                    #(&& ::std::cmp::PartialOrd::le(#value, &#max))*
+                }
+            }
+            Valid::AnyOf(patterns) => {
+                let conditions = patterns.iter().map(eq);
+                quote! { false #(|| #conditions)* }
+            }
+            Valid::Unsupported(feature) => unsupported(id, feature),
+        }
+    }
+}
+
+impl std::fmt::Display for Valid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Valid::Eq(eq) => write!(f, "== {}", eq),
+            Valid::Range { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "in {}..={}", min, max),
+                (Some(min), None) => write!(f, ">= {}", min),
+                (None, Some(max)) => write!(f, "<= {}", max),
+                (None, None) => write!(f, "anything"),
+            },
+            Valid::AnyOf(values) => {
+                let values: Vec<_> = values.iter().map(Pattern::to_string).collect();
+                write!(f, "one of [{}]", values.join(", "))
+            }
+            Valid::Unsupported(feature) => write!(f, "{}", feature),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -472,7 +2689,10 @@ mod tests {
         ];
         let logics = vec![
             Logic::FixedContents(vec![0, 1]),
-            Logic::Type(Type::UserDefined(Ident::new("MyType", Span::call_site()))),
+            Logic::Type(Type::UserDefined {
+                id: Ident::new("MyType", Span::call_site()),
+                borrowed: false,
+            }),
             Logic::Type(Type::BuiltIn {
                 ty: BuiltInType::U16,
                 en: None,
@@ -484,6 +2704,11 @@ mod tests {
             Logic::Size(Size::Eos),
         ];
 
+        let serde = if cfg!(feature = "serde") {
+            quote! { #[serde(with = "::kaitai::__private::serde_repr::hex")] }
+        } else {
+            TokenStream::new()
+        };
         let expected = vec![
             quote! {},
             quote! {
@@ -500,6 +2725,7 @@ mod tests {
             },
             quote! {
                 #[doc = ""]
+                #serde
                 // Yes the space has to be there. No I don't know why.
                 pub vibe: ::std::vec::Vec<::std::vec::Vec<u8> >
             },
@@ -515,11 +2741,264 @@ mod tests {
                     id,
                     doc,
                     repeat,
+                    valid: None,
                     logic,
+                    options: Options::default(),
+                    lazy: false,
+                    strict: false,
+                    endian: None,
+                    decides_endian: Vec::new(),
                 }
                 .field_definition()
             })
             .zip(expected)
             .for_each(|(def, expected)| assert_eq!(def.to_string(), expected.to_string()));
     }
+
+    #[test]
+    fn patterns() {
+        let enum_pattern = |en: &str, variant: &str| Pattern::Enum {
+            en: en.to_owned(),
+            variant: variant.to_owned(),
+        };
+
+        assert_eq!(Pattern::from("_"), Pattern::Default);
+        assert_eq!(Pattern::from("true"), Pattern::Bool(true));
+        assert_eq!(Pattern::from("-12"), Pattern::Int(-12));
+        assert_eq!(Pattern::from("0x1_f"), Pattern::Int(31));
+        assert_eq!(Pattern::from("0b101"), Pattern::Int(5));
+        assert_eq!(Pattern::from("\"RIFF\""), Pattern::Str("RIFF".to_owned()));
+        assert_eq!(Pattern::from("'RIFF'"), Pattern::Str("RIFF".to_owned()));
+        assert_eq!(
+            Pattern::from("[0x50, 0x4b, 3]"),
+            Pattern::Bytes(vec![0x50, 0x4b, 3])
+        );
+        assert_eq!(Pattern::from("fourcc::fmt"), enum_pattern("fourcc", "fmt"));
+        assert_eq!(
+            Pattern::from("riff::fourcc::fmt"),
+            enum_pattern("fourcc", "fmt")
+        );
+
+        assert_eq!(
+            Pattern::from("chunk_type::json")
+                .into_token_stream()
+                .to_string(),
+            quote! { ChunkType::Json }.to_string()
+        );
+    }
+
+    #[test]
+    fn missing_variants() {
+        let owner = Ident::new("Chunk", Span::call_site());
+        let id = Ident::new("body", Span::call_site());
+        let switch = |cases: &[(&str, &str)]| {
+            let cases = cases
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            Switch::new(&owner, &id, "kind", cases)
+        };
+        let enums = [(
+            "Kind".to_owned(),
+            vec!["json".to_owned(), "bin".to_owned(), "text".to_owned()],
+        )]
+        .into_iter()
+        .collect();
+
+        let partial = switch(&[("kind::json", "json"), ("kind::text", "json")]);
+        assert_eq!(partial.missing_variants(&enums), ["kind::bin"]);
+        assert_eq!(partial.variants.len(), 1);
+        assert_eq!(partial.ident.to_string(), "ChunkBody");
+
+        let default = switch(&[("kind::json", "json"), ("_", "u4")]);
+        assert!(default.missing_variants(&enums).is_empty());
+
+        let ints = switch(&[("1", "json")]);
+        assert!(ints.missing_variants(&enums).is_empty());
+    }
+
+    #[test]
+    fn int_case_ranges() {
+        let owner = Ident::new("Chunk", Span::call_site());
+        let id = Ident::new("body", Span::call_site());
+        let check = |keys: &[&str], ty: BuiltInType| {
+            let cases = keys
+                .iter()
+                .map(|k| (k.to_string(), "u4".to_owned()))
+                .collect();
+            let mut switch = Switch::new(&owner, &id, "kind", cases);
+            switch.check_int_cases(&id, &ty);
+            switch.error
+        };
+
+        assert_eq!(check(&["0", "0xff", "_"], BuiltInType::U8), None);
+        assert_eq!(check(&["-128", "127"], BuiltInType::I8), None);
+        assert_eq!(check(&["0xffffffffffffffff"], BuiltInType::U64), None);
+        assert_eq!(
+            check(&["1", "256", "-1"], BuiltInType::U8).unwrap(),
+            "case `256`, `-1` doesn't fit in u1, the type of kind, used by attribute body"
+        );
+        assert!(check(&["128"], BuiltInType::I8).is_some());
+        assert!(check(&["0x10000"], BuiltInType::U16).is_some());
+    }
+
+    #[test]
+    fn repeat_until() {
+        let attr = Attribute {
+            id: Ident::new("entries", Span::call_site()),
+            doc: Doc::new(),
+            repeat: Some(Repeat::Until("_ == 0".to_owned())),
+            valid: None,
+            logic: Logic::Type(Type::BuiltIn {
+                ty: BuiltInType::U8,
+                en: None,
+            }),
+            options: Options::default(),
+            lazy: false,
+            strict: false,
+            endian: None,
+            decides_endian: Vec::new(),
+        };
+
+        assert_eq!(
+            attr.write_statement(Endianness::Le).to_string(),
+            quote! {
+                for item in self.entries.iter() {
+                    buf.write_u1(*item)?;
+                }
+            }
+            .to_string()
+        );
+        assert!(attr.read_statement(Endianness::Le).to_string().contains(
+            "compile_error ! (\"repeat: until isn't supported yet, used by attribute entries\")"
+        ));
+    }
+
+    #[test]
+    fn endian_decision() {
+        let attr = |id: &str, repeat: Option<Repeat>| Attribute {
+            id: Ident::new(id, Span::call_site()),
+            doc: Doc::new(),
+            repeat,
+            valid: None,
+            logic: Logic::Type(Type::BuiltIn {
+                ty: BuiltInType::U8,
+                en: None,
+            }),
+            options: Options::default(),
+            lazy: false,
+            strict: false,
+            endian: None,
+            decides_endian: Vec::new(),
+        };
+        let mut attrs = Attributes(vec![attr("kind", None), attr("kinds", Some(Repeat::Eos))]);
+        let owner = Ident::new("Example", Span::call_site());
+        let ty = Ident::new("Record", Span::call_site());
+        let switch = |on: &str| {
+            let cases = vec![
+                ("1".to_owned(), "le".to_owned()),
+                ("2".to_owned(), "be".to_owned()),
+            ];
+            EndianSwitch::new(&ty, on, cases).unwrap()
+        };
+
+        assert_eq!(
+            attrs.set_endian_decision(&owner, Some(&ty), &switch("_root.size")),
+            Err(
+                "the endianness of Record switches on size, which isn't an attribute of Example"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            attrs.set_endian_decision(&owner, None, &switch("kinds")),
+            Err(
+                "the endianness of Example switches on kinds, which isn't a single value"
+                    .to_owned()
+            )
+        );
+        assert!(attrs
+            .set_endian_decision(&owner, Some(&ty), &switch("_root.kind"))
+            .is_ok());
+        assert!(attrs
+            .set_endian_decision(&owner, None, &switch("kind"))
+            .is_ok());
+        let read = attrs.0[0].read_statement(Endianness::Le).to_string();
+        assert!(read.contains("parse_state . set_type_le (\"Record\" , match self . kind {"));
+        assert!(read.contains(
+            "let is_le = (match self . kind { 1 => :: std :: option :: Option :: Some (true) ,"
+        ));
+        assert!(read.contains("parse_state . set_le (:: std :: option :: Option :: Some (is_le))"));
+        assert!(EndianSwitch::new(&ty, "_io.size", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn valid_constraints() {
+        let id = Ident::new("magic", Span::call_site());
+        let scalar = |s: &str| Scalar(s.to_owned());
+        let int = Logic::Type(Type::BuiltIn {
+            ty: BuiltInType::I16,
+            en: None,
+        });
+        let bytes = Logic::Size(Size::Eos);
+        let condition = |valid: Valid| valid.condition(&id, &quote! { value }).to_string();
+
+        let signed = Valid::from(de::attr::Valid::Eq(scalar("-1"))).with_logic(&int);
+        assert_eq!(signed.to_string(), "== -1");
+        assert_eq!(
+            condition(signed),
+            quote! { ::std::cmp::PartialEq::eq(value, &-1) }.to_string()
+        );
+
+        let magic = Valid::from(de::attr::Valid::Eq(scalar("[0x4b, 0x53]"))).with_logic(&bytes);
+        assert_eq!(
+            condition(magic),
+            quote! { ::std::convert::AsRef::<[u8]>::as_ref(value) == &[75, 83] }.to_string()
+        );
+
+        let unsupported = [
+            Valid::from(de::attr::Valid::Constraints {
+                eq: None,
+                min: None,
+                max: None,
+                any_of: None,
+                expr: Some("_ < 3".to_owned()),
+            }),
+            Valid::from(de::attr::Valid::Eq(scalar("len_magic + 1"))),
+            Valid::from(de::attr::Valid::Eq(scalar("\"KS\""))).with_logic(&bytes),
+            Valid::from(de::attr::Valid::Eq(scalar("1"))).with_logic(&bytes),
+        ];
+        for valid in unsupported {
+            assert!(matches!(valid, Valid::Unsupported(_)));
+            assert!(condition(valid).starts_with(":: std :: compile_error !"));
+        }
+    }
+
+    #[test]
+    fn opaque_types() {
+        let attr = Ident::new("body", Span::call_site());
+        let defined = ["Chunk".to_owned()].into_iter().collect();
+        let png = OpaqueType {
+            path: Some(syn::parse_quote! { crate::png::Png }),
+            eq: true,
+        };
+        let types = [("Png".to_owned(), png)].into_iter().collect();
+        let resolve = |type_ref: &str| {
+            let mut ty = Type::from((type_ref.to_owned(), None));
+            ty.set_opaque(&attr, true, &defined, &types);
+            (ty.ty().to_string(), ty.is_float(&HashSet::new()))
+        };
+
+        assert_eq!(resolve("chunk"), ("Chunk".to_owned(), false));
+        assert_eq!(resolve("png"), ("crate :: png :: Png".to_owned(), false));
+        assert_eq!(resolve("gif"), ("Gif".to_owned(), true));
+        assert_eq!(resolve("u4"), ("u32".to_owned(), false));
+    }
+
+    #[test]
+    #[should_panic(expected = "attribute body has unknown type Gif")]
+    fn unknown_types() {
+        let attr = Ident::new("body", Span::call_site());
+        let mut ty = Type::from(("gif".to_owned(), None));
+        ty.set_opaque(&attr, false, &HashSet::new(), &HashMap::new());
+    }
 }
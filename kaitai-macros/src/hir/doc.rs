@@ -16,6 +16,11 @@ impl Doc {
             },
         }
     }
+
+    /// The `doc` key of the documented item, without its references or metadata.
+    pub fn text(&self) -> &str {
+        &self.doc.doc
+    }
 }
 
 impl From<(Option<MetaDoc>, doc::Doc)> for Doc {
@@ -1,27 +1,148 @@
-use crate::{de, hir::doc::Doc, util::sc_to_ucc};
+use crate::{
+    de,
+    hir::{attr::BuiltInType, doc::Doc, ty::derive_attribute},
+    util::sc_to_ucc,
+};
 
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::{quote, ToTokens};
 
 #[derive(Clone, Debug)]
 pub struct Enumeration {
     ident: Ident,
     variants: Vec<Variant>,
+    /// The variant holding values missing from the enum definition.
+    unknown: Ident,
+    /// The integer type representing the enum, see [`set_repr`](Enumeration::set_repr).
+    repr: BuiltInType,
+    /// The traits derived on the annotated struct.
+    user_derives: Vec<syn::Path>,
 }
 
 impl From<(&str, de::en::Enum)> for Enumeration {
     fn from((id, en): (&str, de::en::Enum)) -> Self {
-        Self {
-            ident: Ident::new(&sc_to_ucc(&id), Span::call_site()),
-            variants: en
-                .0
-                .into_iter()
-                .map(|(value, de::en::EnumValue { id, doc })| Variant {
+        let mut variants: Vec<_> =
+            en.0.into_iter()
+                .map(|(value, de::en::EnumValue { id, orig_id, doc })| Variant {
                     doc: (None, doc).into(),
                     ident: Ident::new(&sc_to_ucc(&id), Span::call_site()),
+                    name: id,
+                    orig_id,
                     value,
                 })
-                .collect(),
+                .collect();
+        // The values are deserialized into a map, so they are sorted to keep the generated code
+        // the same between builds.
+        variants.sort_by_key(|v| v.value);
+
+        let unknown = if variants.iter().any(|v| v.ident == "Unknown") {
+            "UnknownValue"
+        } else {
+            "Unknown"
+        };
+
+        let mut en = Self {
+            ident: Ident::new(&sc_to_ucc(&id), Span::call_site()),
+            variants,
+            unknown: Ident::new(unknown, Span::call_site()),
+            repr: BuiltInType::U8,
+            user_derives: Vec::new(),
+        };
+        en.set_repr(&[]);
+        en
+    }
+}
+
+impl Enumeration {
+    pub fn ident(&self) -> &Ident {
+        &self.ident
+    }
+
+    /// Returns the ids of the variants in the KSY file.
+    pub fn variant_names(&self) -> impl Iterator<Item = &str> {
+        self.variants.iter().map(|v| v.name.as_str())
+    }
+
+    pub fn set_user_derives(&mut self, user_derives: &[syn::Path]) {
+        self.user_derives = user_derives.to_vec();
+    }
+
+    /// Sets the integer type representing the enum to the smallest one that can hold the values
+    /// of the enum, and the values of any of `field_types`, the types of the attributes of the
+    /// enum.
+    pub fn set_repr(&mut self, field_types: &[BuiltInType]) {
+        let layouts = field_types
+            .iter()
+            .map(|ty| {
+                ty.int_layout()
+                    .unwrap_or_else(|| panic!("enum {} is used by a float", self.ident))
+            })
+            .collect::<Vec<_>>();
+
+        let signed =
+            layouts.iter().any(|(signed, _)| *signed) || self.variants.iter().any(|v| v.value < 0);
+        let fits = |value: i128, bits: u32| {
+            if signed {
+                value >= -(1 << (bits - 1)) && value < (1 << (bits - 1))
+            } else {
+                value >= 0 && value < (1 << bits)
+            }
+        };
+        // Unsigned attributes of an enum with signed values need a wider signed type.
+        let field_bits = layouts
+            .iter()
+            .map(|&(s, bits)| if signed && !s { bits * 2 } else { bits });
+        let value_bits = self.variants.iter().map(|v| {
+            [8, 16, 32, 64]
+                .into_iter()
+                .find(|&bits| fits(v.value, bits))
+                .unwrap_or(128)
+        });
+        let bits = field_bits.chain(value_bits).max().unwrap_or(8);
+        if bits > 64 {
+            panic!("enum {} has values that don't fit in 64 bits", self.ident);
+        }
+        self.repr = BuiltInType::integer(signed, bits);
+    }
+}
+
+impl Enumeration {
+    /// Returns the implementations of serde's `Serialize` and `Deserialize`, representing the
+    /// variants by their ids in the KSY file, and values missing from the enum by the values.
+    fn serde_impls(&self) -> TokenStream {
+        let ident = &self.ident;
+        let repr = &self.repr;
+        let names = self.variant_names();
+        quote! {
+            #[automatically_derived]
+            impl ::kaitai::__private::serde::Serialize for #ident {
+                fn serialize<S: ::kaitai::__private::serde::Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+                    match self.name() {
+                        ::std::option::Option::Some(name) => serializer.serialize_str(name),
+                        ::std::option::Option::None => ::kaitai::__private::serde::Serialize::serialize(&self.value(), serializer),
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl<'de> ::kaitai::__private::serde::Deserialize<'de> for #ident {
+                fn deserialize<D: ::kaitai::__private::serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+                    use ::kaitai::__private::serde::de::Error as _;
+                    use ::kaitai::__private::serde_repr::EnumRepr;
+                    let value = match <EnumRepr as ::kaitai::__private::serde::Deserialize>::deserialize(deserializer)? {
+                        EnumRepr::Name(name) => {
+                            return name
+                                .parse()
+                                .map_err(|_| D::Error::unknown_variant(&name, &[#(#names),*]));
+                        }
+                        EnumRepr::Signed(n) => <#repr as ::std::convert::TryFrom<i64>>::try_from(n).ok(),
+                        EnumRepr::Unsigned(n) => <#repr as ::std::convert::TryFrom<u64>>::try_from(n).ok(),
+                    };
+                    value
+                        .map(Self::from_value::<#repr>)
+                        .ok_or_else(|| D::Error::custom("enum value out of range"))
+                }
+            }
         }
     }
 }
@@ -29,28 +150,162 @@ impl From<(&str, de::en::Enum)> for Enumeration {
 impl ToTokens for Enumeration {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let ident = &self.ident;
+        let repr = &self.repr;
         let variant_defs = self.variants.iter().map(|v| v.def());
         let variant_match_arms = self.variants.iter().map(|v| v.match_arm());
+        let variant_value_arms = self.variants.iter().map(|v| v.value_arm());
+        let variant_name_arms = self.variants.iter().map(|v| v.name_arm());
+        let variant_orig_id_arms = self.variants.iter().map(|v| v.orig_id_arm());
+        let variant_parse_arms = self.variants.iter().map(|v| v.parse_arm());
+        let variant_idents = self.variants.iter().map(|v| &v.ident);
+        let unknown = &self.unknown;
+        let derive = derive_attribute(
+            &[
+                "Clone",
+                "Copy",
+                "Debug",
+                "PartialEq",
+                "Eq",
+                "Hash",
+                "PartialOrd",
+                "Ord",
+            ],
+            &self.user_derives,
+        );
+        let from_value_doc = format!(
+            " Returns the variant with the value `n`, or [`{0}`](Self::{0}) if there is none.",
+            unknown
+        );
+        let from_str_doc = format!(
+            " Parses the id of a variant in the KSY file, or a value, as written by `Display`. \
+             Values missing from the enum definition are parsed as [`{0}`](Self::{0}).",
+            unknown
+        );
+
+        let serde_impls = if cfg!(feature = "serde") {
+            self.serde_impls()
+        } else {
+            TokenStream::new()
+        };
 
         tokens.extend(quote! {
-            #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-            // TODO: Is this repr ok?
-            #[repr(u64)]
+            #derive
             pub enum #ident {
-                #(#variant_defs),*
+                #(#variant_defs,)*
+                /// A value missing from the enum definition, e.g. one added by a later version of
+                /// the format.
+                #unknown(#repr),
             }
 
             impl #ident {
-                // TODO: For some reason using an Into bound on N doesn't work
-                // so I have to use this weird where clause. TODO: Add doc
-                // comment for this function.
-                pub fn n<N>(n: N) -> Option<Self> where u64: From<N> {
-                    match u64::from(n) {
-                        #(#variant_match_arms),*,
+                /// The variants of the enum definition, in the order of their values.
+                pub const ALL: &'static [Self] = &[#(Self::#variant_idents),*];
+
+                /// Returns the variant with the value `n`, or `None` if there is none.
+                pub fn n<N>(n: N) -> Option<Self> where #repr: From<N> {
+                    match #repr::from(n) {
+                        #(#variant_match_arms,)*
                         _ => None,
                     }
                 }
+
+                #[doc = #from_value_doc]
+                pub fn from_value<N>(n: N) -> Self where #repr: From<N> {
+                    let n = #repr::from(n);
+                    Self::n::<#repr>(n).unwrap_or(Self::#unknown(n))
+                }
+
+                /// Returns the value of the variant, as specified in the enum definition.
+                pub fn value(&self) -> #repr {
+                    match self {
+                        #(#variant_value_arms,)*
+                        Self::#unknown(value) => *value,
+                    }
+                }
+
+                /// Returns the id of the variant in the KSY file, or `None` for a value missing
+                /// from the enum definition.
+                pub fn name(&self) -> ::std::option::Option<&'static str> {
+                    match self {
+                        #(#variant_name_arms,)*
+                        Self::#unknown(_) => ::std::option::Option::None,
+                    }
+                }
+
+                /// Returns the id of the variant in the original documentation of the format,
+                /// given by `-orig-id`, if there is one.
+                pub fn orig_id(&self) -> ::std::option::Option<&'static str> {
+                    match self {
+                        #(#variant_orig_id_arms,)*
+                        Self::#unknown(_) => ::std::option::Option::None,
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl ::std::fmt::Display for #ident {
+                /// Writes the id of the variant in the KSY file, or the value of a value missing
+                /// from the enum definition.
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    match self.name() {
+                        ::std::option::Option::Some(name) => f.write_str(name),
+                        ::std::option::Option::None => ::std::fmt::Display::fmt(&self.value(), f),
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl ::std::str::FromStr for #ident {
+                type Err = ::kaitai::error::Error;
+
+                #[doc = #from_str_doc]
+                fn from_str(s: &str) -> ::kaitai::error::Result<Self> {
+                    match s {
+                        #(#variant_parse_arms,)*
+                        _ => s
+                            .parse::<#repr>()
+                            .map(Self::from_value::<#repr>)
+                            .map_err(|_| ::kaitai::error::Error::NoEnumMatch),
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl ::std::convert::TryFrom<i64> for #ident {
+                type Error = ::kaitai::error::Error;
+
+                /// Returns the variant with the value `n`, failing with `NoEnumMatch` if there is
+                /// none.
+                fn try_from(n: i64) -> ::kaitai::error::Result<Self> {
+                    <#repr as ::std::convert::TryFrom<i64>>::try_from(n)
+                        .ok()
+                        .and_then(Self::n::<#repr>)
+                        .ok_or(::kaitai::error::Error::NoEnumMatch)
+                }
             }
+
+            #[automatically_derived]
+            impl ::std::convert::TryFrom<u64> for #ident {
+                type Error = ::kaitai::error::Error;
+
+                /// Returns the variant with the value `n`, failing with `NoEnumMatch` if there is
+                /// none.
+                fn try_from(n: u64) -> ::kaitai::error::Result<Self> {
+                    <#repr as ::std::convert::TryFrom<u64>>::try_from(n)
+                        .ok()
+                        .and_then(Self::n::<#repr>)
+                        .ok_or(::kaitai::error::Error::NoEnumMatch)
+                }
+            }
+
+            #[automatically_derived]
+            impl ::std::convert::From<#ident> for #repr {
+                fn from(en: #ident) -> Self {
+                    en.value()
+                }
+            }
+
+            #serde_impls
         })
     }
 }
@@ -59,20 +314,99 @@ impl ToTokens for Enumeration {
 pub struct Variant {
     doc: Doc,
     ident: Ident,
-    value: u64,
+    /// The id of the variant in the KSY file.
+    name: String,
+    orig_id: Option<String>,
+    value: i128,
 }
 
 impl Variant {
+    /// Returns the value as a literal without a suffix, so that it has the type of the repr.
+    fn literal(&self) -> Literal {
+        Literal::i128_unsuffixed(self.value)
+    }
+
+    /// Returns the definition of the variant. It has no explicit discriminant, as its value is
+    /// given by [`value_arm`](Variant::value_arm), and the implicit discriminant of the `Unknown`
+    /// variant after it would overflow for the largest value of the repr.
     fn def(&self) -> TokenStream {
-        let Variant { doc, ident, value } = self;
+        let Variant { doc, ident, .. } = self;
         quote! {
             #doc
-            #ident = #value
+            #ident
         }
     }
 
     fn match_arm(&self) -> TokenStream {
-        let Variant { ident, value, .. } = self;
+        let ident = &self.ident;
+        let value = self.literal();
         quote! { #value => ::std::option::Option::Some(Self::#ident) }
     }
+
+    fn value_arm(&self) -> TokenStream {
+        let ident = &self.ident;
+        let value = self.literal();
+        quote! { Self::#ident => #value }
+    }
+
+    fn name_arm(&self) -> TokenStream {
+        let Variant { ident, name, .. } = self;
+        quote! { Self::#ident => ::std::option::Option::Some(#name) }
+    }
+
+    fn orig_id_arm(&self) -> TokenStream {
+        let ident = &self.ident;
+        match self.orig_id {
+            Some(ref orig_id) => quote! { Self::#ident => ::std::option::Option::Some(#orig_id) },
+            None => quote! { Self::#ident => ::std::option::Option::None },
+        }
+    }
+
+    fn parse_arm(&self) -> TokenStream {
+        let Variant { ident, name, .. } = self;
+        quote! { #name => ::std::result::Result::Ok(Self::#ident) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enumeration(values: &[i128]) -> Enumeration {
+        Enumeration {
+            ident: Ident::new("Example", Span::call_site()),
+            variants: values
+                .iter()
+                .map(|&value| Variant {
+                    doc: Doc::new(),
+                    ident: Ident::new(&format!("V{}", value.unsigned_abs()), Span::call_site()),
+                    name: format!("v{}", value.unsigned_abs()),
+                    orig_id: None,
+                    value,
+                })
+                .collect(),
+            unknown: Ident::new("Unknown", Span::call_site()),
+            repr: BuiltInType::U8,
+            user_derives: Vec::new(),
+        }
+    }
+
+    fn repr(values: &[i128], field_types: &[BuiltInType]) -> String {
+        let mut en = enumeration(values);
+        en.set_repr(field_types);
+        en.repr.to_token_stream().to_string()
+    }
+
+    #[test]
+    fn reprs() {
+        assert_eq!(repr(&[1, 6, 17], &[BuiltInType::U8]), "u8");
+        assert_eq!(repr(&[1, 6, 17], &[BuiltInType::U32]), "u32");
+        assert_eq!(repr(&[1, 300], &[]), "u16");
+        assert_eq!(repr(&[-1, 1], &[BuiltInType::I16]), "i16");
+        assert_eq!(repr(&[-1, 1], &[BuiltInType::U8]), "i16");
+        assert_eq!(repr(&[1], &[BuiltInType::U8, BuiltInType::I8]), "i16");
+        assert_eq!(repr(&[200], &[BuiltInType::I8]), "i16");
+        assert_eq!(repr(&[-1, 1], &[BuiltInType::I64]), "i64");
+        assert_eq!(repr(&[u64::MAX as i128], &[BuiltInType::U64]), "u64");
+    }
 }
@@ -1,10 +1,146 @@
 pub use crate::de::meta::Endianness;
 
+use crate::hir::attr::Pattern;
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, ToTokens};
+
 impl From<Endianness> for &'static str {
     fn from(e: Endianness) -> Self {
         match e {
             Endianness::Le => "le",
             Endianness::Be => "be",
+            Endianness::Runtime => unreachable!("the endianness is decided at runtime"),
+            Endianness::Unspecified => unreachable!("the endianness is unspecified"),
+        }
+    }
+}
+
+/// The endianness of a type that switches on an attribute, e.g.
+///
+/// ```yaml
+/// endian:
+///   switch-on: _parent.byte_order
+///   cases:
+///     '"II"': le
+///     '"MM"': be
+/// ```
+#[derive(Clone, Debug)]
+pub struct EndianSwitch {
+    /// The type owning the attribute switched on.
+    base: EndianBase,
+    /// The attribute that the endianness depends on.
+    on: Ident,
+    /// The cases of the switch, in the order they are written, and whether they are little
+    /// endian.
+    cases: Vec<(Pattern, bool)>,
+}
+
+/// The type owning the attribute an [`EndianSwitch`] switches on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndianBase {
+    /// The type itself, e.g. `switch-on: byte_order`. The endianness is decided once the
+    /// attribute is read, so the attributes before it can't depend on it.
+    Own,
+    /// The type reading the type, e.g. `switch-on: _parent.byte_order`.
+    Parent,
+    /// The root type, e.g. `switch-on: _root.byte_order`. The root type decides the endianness
+    /// once the attribute is read.
+    Root,
+}
+
+impl EndianSwitch {
+    /// Creates the endianness of the type `ty`, from its `switch-on` and `cases` keys. The value
+    /// switched on has to be an attribute of `ty`, of the type reading it through `_parent`, or of
+    /// the root type through `_root`. Returns an error message for other expressions, which
+    /// aren't supported yet.
+    pub fn new(ty: &Ident, on: &str, cases: Vec<(String, String)>) -> Result<Self, String> {
+        // TODO: expressions
+        let (base, attr) = if let Some(attr) = on.strip_prefix("_parent.") {
+            (EndianBase::Parent, attr)
+        } else if let Some(attr) = on.strip_prefix("_root.") {
+            (EndianBase::Root, attr)
+        } else {
+            (EndianBase::Own, on)
+        };
+        if attr.contains('.') || attr.starts_with('_') || syn::parse_str::<Ident>(attr).is_err() {
+            return Err(format!(
+                "switch-on `{}` in the endianness of {} isn't supported yet, only attributes of \
+                 the type, of `_parent` or of `_root` are",
+                on, ty
+            ));
+        }
+        let cases = cases
+            .into_iter()
+            .map(|(key, endianness)| {
+                let is_le = match endianness.as_ref() {
+                    "le" => true,
+                    "be" => false,
+                    _ => panic!("invalid endianness `{}` in {}", endianness, ty),
+                };
+                (Pattern::from(key.as_ref()), is_le)
+            })
+            .collect();
+        Ok(Self {
+            base,
+            on: Ident::new(attr, Span::call_site()),
+            cases,
+        })
+    }
+
+    /// The type owning the attribute the endianness depends on.
+    pub fn base(&self) -> EndianBase {
+        self.base
+    }
+
+    /// Makes the endianness depend on an attribute of the type itself, for the root type, whose
+    /// `_root` is itself.
+    pub fn set_own(&mut self) {
+        self.base = EndianBase::Own;
+    }
+
+    /// The attribute the endianness depends on.
+    pub fn on(&self) -> &Ident {
+        &self.on
+    }
+
+    /// Returns a [`TokenStream`] of an `Option<bool>`, whether the type is little endian, or
+    /// `None` if no case matches the attribute switched on. If `on_self` is set, it is accessed
+    /// through `self` rather than through a variable.
+    pub fn expr(&self, on_self: bool) -> TokenStream {
+        let on = &self.on;
+        let mut on = if on_self {
+            quote! { self.#on }
+        } else {
+            on.into_token_stream()
+        };
+        // Strings and byte arrays are matched as slices.
+        if self
+            .cases
+            .iter()
+            .any(|(pattern, _)| matches!(pattern, Pattern::Str(_) | Pattern::Bytes(_)))
+        {
+            on = quote! { &*#on };
+        }
+        let arms = self
+            .cases
+            .iter()
+            .filter(|(pattern, _)| !matches!(pattern, Pattern::Default))
+            .map(|(pattern, is_le)| quote! { #pattern => ::std::option::Option::Some(#is_le) });
+        let default = match self
+            .cases
+            .iter()
+            .find(|(pattern, _)| matches!(pattern, Pattern::Default))
+        {
+            Some((_, is_le)) => quote! { ::std::option::Option::Some(#is_le) },
+            None => quote! { ::std::option::Option::None },
+        };
+        quote! {
+            match #on {
+                #(#arms,)*
+                #[allow(unreachable_patterns)]
+                _ => #default,
+            }
         }
     }
 }
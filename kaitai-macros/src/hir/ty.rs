@@ -1,35 +1,104 @@
 use crate::{
+    args::{OpaqueType, Options},
     de,
     hir::{
-        attr::{Attribute, Attributes},
+        attr::{check_encoding, Attribute, Attributes, BuiltInType},
         doc::Doc,
         en::Enumeration,
-        meta::Endianness,
+        meta::{EndianBase, EndianSwitch, Endianness},
         param::Parameter,
     },
     util::sc_to_ucc,
 };
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use proc_macro2::{Ident, Span};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
+use syn::punctuated::Punctuated;
+
+/// Returns the traits derived for a generated struct, which derives `Eq` and `Ord` if `eq` is
+/// set.
+pub fn struct_derives(eq: bool) -> &'static [&'static str] {
+    if eq {
+        &["Debug", "PartialEq", "Eq", "PartialOrd", "Ord"]
+    } else {
+        &["Debug", "PartialEq", "PartialOrd"]
+    }
+}
+
+/// Returns the `derive` attribute of a generated type, deriving `derives` and the traits derived
+/// by the user that aren't among them. With the `serde` feature, the serde traits derived by the
+/// user are left out as well, as they are derived by [`serde_attributes`].
+pub fn derive_attribute(derives: &[&str], user_derives: &[syn::Path]) -> TokenStream {
+    let serde_derives: &[&str] = if cfg!(feature = "serde") {
+        &["Serialize", "Deserialize"]
+    } else {
+        &[]
+    };
+    let user_derives = user_derives.iter().filter(|path| {
+        let name = path.segments.last().map(|s| s.ident.to_string());
+        !derives
+            .iter()
+            .chain(serde_derives)
+            .any(|d| Some(*d) == name.as_deref())
+    });
+    let derives = derives.iter().map(|d| Ident::new(d, Span::call_site()));
+    quote::quote! { #[derive(#(#derives,)* #(#user_derives),*)] }
+}
+
+/// Returns the attributes deriving serde's `Serialize`, and `Deserialize` if `deserialize` is
+/// set, for a generated type, or nothing without the `serde` feature. Types that borrow from the
+/// bytes being parsed can't be deserialized, as their byte arrays are represented as strings.
+pub fn serde_attributes(deserialize: bool) -> TokenStream {
+    if !cfg!(feature = "serde") {
+        return TokenStream::new();
+    }
+    let deserialize =
+        deserialize.then(|| quote::quote! { ::kaitai::__private::serde::Deserialize });
+    quote::quote! {
+        #[derive(::kaitai::__private::serde::Serialize, #deserialize)]
+        #[serde(crate = "::kaitai::__private::serde")]
+    }
+}
 
 #[derive(Debug)]
 pub struct Type {
     id: Ident,
+    /// The endianness of the type, which is `Runtime` if it switches, or is inherited from a type
+    /// that switches.
     endianness: Endianness,
+    /// The switch deciding the endianness of the type, if it has one, or an error message if it
+    /// isn't supported.
+    endian_switch: Option<Result<EndianSwitch, String>>,
     doc: Doc,
     params: Vec<Parameter>,
     seq: Attributes,
     types: Vec<Type>,
     instances: HashMap<String, Attribute>,
     enums: Vec<Enumeration>,
+    /// Whether the type borrows from the bytes being parsed, and so has a lifetime.
+    borrowed: bool,
+    /// Whether the type can derive `Eq` and `Ord`, i.e. it doesn't contain any floats.
+    eq: bool,
+    /// Whether the offsets of the attributes are recorded, i.e. `ks-debug` is set.
+    debug: bool,
+    /// Whether the type contains opaque types, which can't be read asynchronously.
+    opaque: bool,
+    /// The traits derived on the annotated struct, which are derived for every generated type.
+    user_derives: Vec<syn::Path>,
+    /// The traits given by the `-derive` key of the type, which replace `user_derives` for the
+    /// type and the types and enums it contains.
+    derive: Option<Vec<syn::Path>>,
+    /// The attributes of the annotated struct other than derives. Only set on the root type.
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
 }
 
 pub struct InheritedMeta {
     pub id: Option<(Ident, bool)>,
     pub endianness: Option<Endianness>,
+    pub options: Options,
 }
 
 impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
@@ -53,15 +122,46 @@ impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
             None => meta_id.unwrap(),
         };
 
-        let endianness = ty
+        let (endianness, endian_switch) = match ty.meta.as_ref().and_then(|m| m.endianness.clone())
+        {
+            Some(de::meta::Endian::Fixed(endianness)) => (endianness, None),
+            Some(de::meta::Endian::Switch { switch_on, cases }) => (
+                Endianness::Runtime,
+                Some(EndianSwitch::new(&id, &switch_on, cases)),
+            ),
+            None => (
+                inherited_meta.endianness.unwrap_or(Endianness::Unspecified),
+                None,
+            ),
+        };
+        if let Some(encoding) = ty.meta.as_ref().and_then(|m| m.encoding.as_ref()) {
+            check_encoding(encoding);
+        }
+        let derive = ty
             .meta
             .as_ref()
-            .and_then(|m| m.endianness)
-            .or(inherited_meta.endianness)
-            .expect("no endianness inherited");
+            .and_then(|m| m.derive.as_ref())
+            .map(|derive| {
+                derive
+                    .iter()
+                    .map(|path| {
+                        syn::parse_str(path)
+                            .unwrap_or_else(|_| panic!("invalid derive `{}` in {}", path, id))
+                    })
+                    .collect()
+            });
+        let mut options = inherited_meta.options;
+        if ty.meta.as_ref().is_some_and(|m| m.ks_debug) {
+            options.ks_debug = true;
+        }
         // TODO: All the meta doc clones.
         let doc = (ty.meta.as_ref().map(|meta| meta.doc.clone()), ty.doc).into();
-        let seq = (ty.meta.as_ref().map(|m| m.doc.clone()), ty.seq)
+        let seq = (
+            ty.meta.as_ref().map(|m| m.doc.clone()),
+            options,
+            &id,
+            ty.seq,
+        )
             .try_into()
             .expect("seq validation failed");
         let types = ty
@@ -71,6 +171,7 @@ impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
                 let inherited_meta = InheritedMeta {
                     id: Some((Ident::new(&sc_to_ucc(&id), Span::call_site()), false)),
                     endianness: Some(endianness),
+                    options,
                 };
                 Type::try_from((inherited_meta, ty)).expect("type validation failed")
             })
@@ -84,6 +185,7 @@ impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
         Ok(Self {
             id,
             endianness,
+            endian_switch,
             doc,
             // TODO
             params: Default::default(),
@@ -92,41 +194,594 @@ impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
             // TODO
             instances: Default::default(),
             enums,
+            borrowed: false,
+            eq: true,
+            debug: options.ks_debug,
+            opaque: false,
+            user_derives: Vec::new(),
+            derive,
+            attrs: Vec::new(),
+            vis: syn::parse_quote! { pub },
+        })
+    }
+}
+
+impl Type {
+    /// Replaces the types that aren't defined in the ksy file with opaque types, i.e. types
+    /// implemented by hand, if `enabled`, and works out which types contain them. `types` gives
+    /// the Rust types of the opaque types, keyed by their names, which otherwise refer to the types
+    /// in scope. This has to be called on the root type once it has been created, before the
+    /// other passes.
+    pub fn resolve_opaque(&mut self, enabled: bool, types: &HashMap<String, OpaqueType>) {
+        let mut defined = HashSet::new();
+        self.find_types(&mut defined);
+        self.set_opaque(enabled, &defined, types);
+        let mut opaque = HashSet::new();
+        while self.find_opaque(&mut opaque) {}
+        self.set_contains_opaque(&opaque);
+    }
+
+    fn find_types(&self, defined: &mut HashSet<String>) {
+        defined.insert(self.id.to_string());
+        for ty in self.types.iter() {
+            ty.find_types(defined);
+        }
+    }
+
+    fn set_opaque(
+        &mut self,
+        enabled: bool,
+        defined: &HashSet<String>,
+        types: &HashMap<String, OpaqueType>,
+    ) {
+        self.seq.set_opaque(enabled, defined, types);
+        for ty in self.types.iter_mut() {
+            ty.set_opaque(enabled, defined, types);
+        }
+    }
+
+    /// Adds the types containing opaque types to `opaque`, returning whether any were added.
+    fn find_opaque(&self, opaque: &mut HashSet<String>) -> bool {
+        let mut changed = false;
+        if !opaque.contains(&self.id.to_string()) && self.seq.has_opaque(opaque) {
+            opaque.insert(self.id.to_string());
+            changed = true;
+        }
+        for ty in self.types.iter() {
+            changed |= ty.find_opaque(opaque);
+        }
+        changed
+    }
+
+    fn set_contains_opaque(&mut self, opaque: &HashSet<String>) {
+        self.opaque = opaque.contains(&self.id.to_string());
+        for ty in self.types.iter_mut() {
+            ty.set_contains_opaque(opaque);
+        }
+    }
+
+    /// Sets up the attributes deciding the endianness of the types that switch their endianness:
+    /// the attributes reading types that switch on an attribute of the type reading them, and the
+    /// attributes switched on by types switching on an attribute of their own or of the root
+    /// type, so that the endianness is decided before reading them. This has to be called on the
+    /// root type once it has been created.
+    pub fn resolve_endian_switches(&mut self) {
+        // `_root` is the root type itself, and it has no `_parent`.
+        if let Some(Ok(endian)) = &mut self.endian_switch {
+            match endian.base() {
+                EndianBase::Own => {}
+                EndianBase::Root => endian.set_own(),
+                EndianBase::Parent => {
+                    let message = format!(
+                        "the endianness of {} switches on `_parent`, but it is the root type",
+                        self.id
+                    );
+                    self.endian_switch = Some(Err(message));
+                }
+            }
+        }
+        let mut switches = HashMap::new();
+        self.find_endian_switches(&mut switches);
+        let mut errors = HashMap::new();
+        for (ty, endian) in switches.iter() {
+            if endian.base() != EndianBase::Root {
+                continue;
+            }
+            let ty = Ident::new(ty, Span::call_site());
+            if let Err(message) = self.seq.set_endian_decision(&self.id, Some(&ty), endian) {
+                errors.insert(ty.to_string(), message);
+            }
+        }
+        self.set_endian_switches(&switches, &errors);
+    }
+
+    fn find_endian_switches(&self, switches: &mut HashMap<String, EndianSwitch>) {
+        if let Some(Ok(endian)) = &self.endian_switch {
+            switches.insert(self.id.to_string(), endian.clone());
+        }
+        for ty in self.types.iter() {
+            ty.find_endian_switches(switches);
+        }
+    }
+
+    /// Sets the endianness switches found by
+    /// [`resolve_endian_switches`](Type::resolve_endian_switches) on the type and the types it
+    /// contains, given the errors of the types switching on an attribute of the root type.
+    fn set_endian_switches(
+        &mut self,
+        switches: &HashMap<String, EndianSwitch>,
+        errors: &HashMap<String, String>,
+    ) {
+        if let Some(message) = errors.get(&self.id.to_string()) {
+            self.endian_switch = Some(Err(message.clone()));
+        }
+        if let Some(Ok(endian)) = &self.endian_switch {
+            if endian.base() == EndianBase::Own {
+                if let Err(message) = self.seq.set_endian_decision(&self.id, None, endian) {
+                    self.endian_switch = Some(Err(message));
+                }
+            }
+        }
+        self.seq.set_endian_switches(&self.id, switches);
+        for ty in self.types.iter_mut() {
+            ty.set_endian_switches(switches, errors);
+        }
+    }
+
+    /// Works out which types borrow from the bytes being parsed, i.e. the types containing byte
+    /// arrays, strings, or other types that borrow, and updates the types and their attributes to
+    /// match. This has to be called on the root type once it has been created.
+    pub fn resolve_borrowed(&mut self) {
+        let mut borrowing = HashSet::new();
+        while self.find_borrowing(&mut borrowing) {}
+        self.set_borrowing(&borrowing);
+    }
+
+    /// Adds the types that borrow to `borrowing`, returning whether any were added.
+    fn find_borrowing(&self, borrowing: &mut HashSet<String>) -> bool {
+        let mut changed = false;
+        if !borrowing.contains(&self.id.to_string()) && self.seq.borrows(borrowing) {
+            borrowing.insert(self.id.to_string());
+            changed = true;
+        }
+        for ty in self.types.iter() {
+            changed |= ty.find_borrowing(borrowing);
+        }
+        changed
+    }
+
+    fn set_borrowing(&mut self, borrowing: &HashSet<String>) {
+        self.borrowed = borrowing.contains(&self.id.to_string());
+        self.seq.set_borrowing(borrowing);
+        for ty in self.types.iter_mut() {
+            ty.set_borrowing(borrowing);
+        }
+    }
+
+    /// Forwards the attributes and visibility of the struct annotated with the macro to the root
+    /// type. The traits it derives are derived for every generated type, as the root type can
+    /// only derive them if the types it contains do, except for the types with a `-derive` key
+    /// and the types inside them.
+    pub fn forward_item(&mut self, item: &syn::ItemStruct) {
+        let mut user_derives = Vec::new();
+        for attr in item.attrs.iter() {
+            if attr.path.is_ident("derive") {
+                let paths = attr
+                    .parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+                    .expect("invalid derive attribute");
+                user_derives.extend(paths);
+            } else {
+                self.attrs.push(attr.clone());
+            }
+        }
+        self.vis = item.vis.clone();
+        self.set_user_derives(&user_derives);
+    }
+
+    fn set_user_derives(&mut self, user_derives: &[syn::Path]) {
+        let user_derives = self.derive.as_deref().unwrap_or(user_derives);
+        self.user_derives = user_derives.to_vec();
+        for en in self.enums.iter_mut() {
+            en.set_user_derives(user_derives);
+        }
+        for ty in self.types.iter_mut() {
+            ty.set_user_derives(user_derives);
+        }
+    }
+
+    /// Works out which types can derive `Eq` and `Ord`, i.e. the types not containing floats, or
+    /// other types containing floats. This has to be called on the root type once it has been
+    /// created.
+    pub fn resolve_eq(&mut self) {
+        let mut floats = HashSet::new();
+        while self.find_floats(&mut floats) {}
+        self.set_eq(&floats);
+    }
+
+    /// Adds the types containing floats to `floats`, returning whether any were added.
+    fn find_floats(&self, floats: &mut HashSet<String>) -> bool {
+        let mut changed = false;
+        if !floats.contains(&self.id.to_string()) && self.seq.has_floats(floats) {
+            floats.insert(self.id.to_string());
+            changed = true;
+        }
+        for ty in self.types.iter() {
+            changed |= ty.find_floats(floats);
+        }
+        changed
+    }
+
+    fn set_eq(&mut self, floats: &HashSet<String>) {
+        self.eq = !floats.contains(&self.id.to_string());
+        self.seq.set_floats(floats);
+        for ty in self.types.iter_mut() {
+            ty.set_eq(floats);
+        }
+    }
+
+    /// Works out the integer type representing each enum from the types of the attributes of the
+    /// enum, which may be in any type. This has to be called on the root type once it has been
+    /// created.
+    pub fn resolve_enum_reprs(&mut self) {
+        let mut types = HashMap::new();
+        self.find_enum_types(&mut types);
+        self.set_enum_reprs(&types);
+    }
+
+    fn find_enum_types(&self, types: &mut HashMap<String, Vec<BuiltInType>>) {
+        self.seq.enum_types(types);
+        for ty in self.types.iter() {
+            ty.find_enum_types(types);
+        }
+    }
+
+    /// Checks the cases of switches on enums against the variants of the enums, which may be in
+    /// any type. This has to be called on the root type once it has been created.
+    pub fn check_switches(&self) {
+        let mut enums = HashMap::new();
+        self.find_enum_variants(&mut enums);
+        self.check_switches_with(&enums);
+    }
+
+    fn find_enum_variants(&self, enums: &mut HashMap<String, Vec<String>>) {
+        for en in self.enums.iter() {
+            enums.insert(
+                en.ident().to_string(),
+                en.variant_names().map(str::to_owned).collect(),
+            );
+        }
+        for ty in self.types.iter() {
+            ty.find_enum_variants(enums);
+        }
+    }
+
+    fn check_switches_with(&self, enums: &HashMap<String, Vec<String>>) {
+        self.seq.check_switches(enums);
+        for ty in self.types.iter() {
+            ty.check_switches_with(enums);
+        }
+    }
+
+    fn set_enum_reprs(&mut self, types: &HashMap<String, Vec<BuiltInType>>) {
+        for en in self.enums.iter_mut() {
+            en.set_repr(
+                types
+                    .get(&en.ident().to_string())
+                    .map_or(&[], Vec::as_slice),
+            );
+        }
+        for ty in self.types.iter_mut() {
+            ty.set_enum_reprs(types);
+        }
+    }
+}
+
+impl Type {
+    /// Returns the definition of the field holding the offsets of the attributes in debug mode.
+    fn debug_field_definition(&self) -> Option<TokenStream> {
+        let serde = if cfg!(feature = "serde") {
+            quote::quote! { #[serde(skip)] }
+        } else {
+            TokenStream::new()
+        };
+        self.debug.then(|| {
+            quote::quote! {
+                #serde
+                _debug: ::kaitai::DebugSpans
+            }
         })
     }
+
+    fn is_runtime_endian(&self) -> bool {
+        matches!(self.endianness, Endianness::Runtime)
+    }
+
+    /// Returns the definition of the field holding whether the type was read as little endian,
+    /// if its endianness is decided at runtime, so that it is written the same way.
+    fn endian_field_definition(&self) -> Option<TokenStream> {
+        self.is_runtime_endian()
+            .then(|| quote::quote! { _is_le: bool })
+    }
+
+    /// Returns the declaration of `is_le`, whether the type is read as little endian, if its
+    /// endianness is decided at runtime. A type switching on an attribute of its own declares it
+    /// once the attribute is read instead, see [`Attributes::set_endian_decision`], and a type
+    /// switching on an attribute of the root type makes it the endianness of the types it reads,
+    /// until the `ParseState` restores the previous endianness when leaving the type.
+    fn endian_init(&self) -> TokenStream {
+        match &self.endian_switch {
+            Some(Err(message)) => quote::quote! { ::std::compile_error!(#message); },
+            Some(Ok(endian)) if endian.base() == EndianBase::Own => TokenStream::new(),
+            Some(Ok(endian)) if endian.base() == EndianBase::Root => {
+                let name = self.id.to_string();
+                quote::quote! {
+                    let is_le = parse_state.type_le(#name)?;
+                    parse_state.set_le(::std::option::Option::Some(is_le));
+                }
+            }
+            _ if self.is_runtime_endian() => quote::quote! { let is_le = parse_state.is_le()?; },
+            _ => TokenStream::new(),
+        }
+    }
+
+    /// Returns the fields set when creating the type, i.e. its stored attributes, whether it was
+    /// read as little endian if that is decided at runtime, and the offsets of the attributes in
+    /// debug mode.
+    fn field_assignments(&self) -> Vec<TokenStream> {
+        self.seq
+            .field_assignments()
+            .map(|id| id.to_token_stream())
+            .chain(
+                self.is_runtime_endian()
+                    .then(|| quote::quote! { _is_le: is_le }),
+            )
+            .chain(self.debug.then(|| quote::quote! { _debug }))
+            .collect()
+    }
+
+    /// Returns the declaration of `_debug`, which the offsets of the attributes are pushed to, in
+    /// debug mode.
+    fn debug_init(&self) -> TokenStream {
+        if self.debug {
+            quote::quote! { let mut _debug = ::kaitai::DebugSpans::default(); }
+        } else {
+            TokenStream::new()
+        }
+    }
 }
 
 impl ToTokens for Type {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let type_defs = self.types.iter().map(|ty| ty.into_token_stream());
         let enum_defs = self.enums.iter().map(|en| en.into_token_stream());
+        let switch_defs = self.seq.switch_definitions(&self.user_derives);
+        let attrs = &self.attrs;
+        let vis = &self.vis;
+        let derive = derive_attribute(struct_derives(self.eq), &self.user_derives);
+        let serde = serde_attributes(!self.borrowed);
         let doc = &self.doc;
         let id = &self.id;
-        let field_defs = self.seq.field_definitions();
-        let var_assignments = self.seq.variable_assignments(self.endianness);
-        let field_assignments = self.seq.field_assignments();
+        let name = id.to_string();
+        let field_defs = self
+            .seq
+            .field_definitions()
+            .chain(self.endian_field_definition())
+            .chain(self.debug_field_definition());
+        let var_assignments = self.seq.variable_assignments(self.endianness, false);
+        let field_assignments = self.field_assignments();
+        let read_statements = self.seq.read_statements(self.endianness);
+        let write_statements = self.seq.write_statements(self.endianness);
+        let check_statements = self.seq.check_statements();
+        let visit_statements = self.seq.visit_statements();
+
+        let (lifetime, elided_lifetime) = if self.borrowed {
+            (quote::quote! { <'a> }, quote::quote! { <'_> })
+        } else {
+            Default::default()
+        };
+        let debug_init = self.debug_init();
+        let endian_init = self.endian_init();
+        let debug_store = if self.debug {
+            quote::quote! { self._debug = _debug; }
+        } else {
+            TokenStream::new()
+        };
+        let (endian_store, endian_load) = if self.is_runtime_endian() {
+            (
+                quote::quote! { self._is_le = is_le; },
+                quote::quote! {
+                    #[allow(unused_variables)]
+                    let is_le = self._is_le;
+                },
+            )
+        } else {
+            Default::default()
+        };
+        let debug_impl = if self.debug {
+            quote::quote! {
+                #[automatically_derived]
+                impl #lifetime #id #lifetime {
+                    /// The offsets in the stream the attributes were read from.
+                    pub fn _debug(&self) -> &::kaitai::DebugSpans {
+                        &self._debug
+                    }
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+        let endian_impl = if self.is_runtime_endian() {
+            quote::quote! {
+                #[automatically_derived]
+                impl #lifetime #id #lifetime {
+                    /// Whether the instance was read as, and is written as, little endian.
+                    pub fn _is_le(&self) -> bool {
+                        self._is_le
+                    }
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let constructor_impl = if self.borrowed {
+            quote::quote! {
+                #[automatically_derived]
+                impl<'a> ::kaitai::KaitaiBorrowedStruct<'a> for #id<'a> {
+                    fn new_with(buf: &mut ::kaitai::__private::BytesStream<'a>, parse_state: &mut ::kaitai::__private::ParseState) -> ::kaitai::error::Result<Self> {
+                        use ::kaitai::__private::KaitaiStream as _;
+                        parse_state.read(#name, buf, |buf, parse_state| {
+                            #endian_init
+                            #debug_init
+                            #(#var_assignments);*;
+                            Ok(Self {
+                                #(#field_assignments),*
+                            })
+                        })
+                    }
+                }
+            }
+        } else {
+            let partial_id = quote::format_ident!("{}Partial", id);
+            let partial_doc = format!(
+                " A partially created [`{}`], see [`Partial`](::kaitai::Partial).",
+                id
+            );
+            let partial_field_defs = self.seq.partial_field_definitions();
+            let partial_statements = self.seq.partial_statements(self.endianness);
+            let partial_serde = serde_attributes(true);
+            quote::quote! {
+                #[doc = #partial_doc]
+                #[derive(Debug, Default, PartialEq)]
+                #partial_serde
+                pub struct #partial_id {
+                    #(#partial_field_defs),*
+                }
+
+                #[automatically_derived]
+                impl ::kaitai::KaitaiStruct for #id {
+                    type Partial = #partial_id;
+
+                    fn new_with<S: ::kaitai::__private::KaitaiStream>(buf: &mut S, parse_state: &mut ::kaitai::__private::ParseState) -> ::kaitai::error::Result<Self> {
+                        parse_state.read(#name, buf, |buf, parse_state| {
+                            #endian_init
+                            #debug_init
+                            #(#var_assignments);*;
+                            Ok(Self {
+                                #(#field_assignments),*
+                            })
+                        })
+                    }
+                    fn read_with<S: ::kaitai::__private::KaitaiStream>(&mut self, buf: &mut S, parse_state: &mut ::kaitai::__private::ParseState) -> ::kaitai::error::Result<()> {
+                        parse_state.read(#name, buf, |buf, parse_state| {
+                            #endian_init
+                            #debug_init
+                            #(#read_statements)*
+                            #debug_store
+                            #endian_store
+                            Ok(())
+                        })
+                    }
+                    #[allow(unused_variables)]
+                    fn new_partial_with<S: ::kaitai::__private::KaitaiStream>(buf: &mut S, parse_state: &mut ::kaitai::__private::ParseState, partial_self: &mut Self::Partial) -> ::kaitai::error::Result<()> {
+                        parse_state.read(#name, buf, |buf, parse_state| {
+                            #endian_init
+                            #(#partial_statements)*
+                            Ok(())
+                        })
+                    }
+                }
+            }
+        };
+
+        // Borrowed types can only be created from a slice, so there is nothing to await. Opaque
+        // types can only be read synchronously.
+        let async_impl = if cfg!(feature = "async") && !self.borrowed && !self.opaque {
+            let var_assignments = self.seq.variable_assignments(self.endianness, true);
+            let field_assignments = self.field_assignments();
+            quote::quote! {
+                #[automatically_derived]
+                impl #id {
+                    /// Create an instance of a `KaitaiStruct` format from an asynchronous stream.
+                    pub async fn new_async<S: ::kaitai::__private::AsyncKaitaiStream>(buf: &mut S) -> ::kaitai::error::Result<Self> {
+                        Self::new_async_with(buf, &::kaitai::ParseOptions::default()).await
+                    }
+                    /// Create an instance of a `KaitaiStruct` format from an asynchronous stream,
+                    /// within the limits given by `options`.
+                    pub async fn new_async_with<S: ::kaitai::__private::AsyncKaitaiStream>(buf: &mut S, options: &::kaitai::ParseOptions) -> ::kaitai::error::Result<Self> {
+                        Self::new_async_in(buf, &mut ::kaitai::__private::ParseState::new(options)).await
+                    }
+                    #[doc(hidden)]
+                    pub async fn new_async_in<S: ::kaitai::__private::AsyncKaitaiStream>(buf: &mut S, parse_state: &mut ::kaitai::__private::ParseState) -> ::kaitai::error::Result<Self> {
+                        // Async closures aren't stable, so this is `ParseState::read` written out.
+                        parse_state.enter(#name)?;
+                        let result: ::kaitai::error::Result<Self> = async {
+                            #endian_init
+                            #debug_init
+                            #(#var_assignments);*;
+                            Ok(Self {
+                                #(#field_assignments),*
+                            })
+                        }.await;
+                        match result {
+                            Ok(value) => {
+                                parse_state.exit();
+                                Ok(value)
+                            }
+                            Err(error) => {
+                                let offset = buf.pos().await;
+                                Err(parse_state.fail(error, offset))
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
 
         tokens.extend(quote::quote! {
             #(#type_defs)*
             #(#enum_defs)*
+            #(#switch_defs)*
 
             #doc
-            // TODO: Pass down attributes from struct
-            #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-            pub struct #id {
+            #(#attrs)*
+            #derive
+            #serde
+            #vis struct #id #lifetime {
                 #(#field_defs),*
             }
 
+            #constructor_impl
+
+            #async_impl
+
+            #debug_impl
+
+            #endian_impl
+
+            #[automatically_derived]
+            impl ::kaitai::KaitaiWrite for #id #elided_lifetime {
+                fn write_to<S: ::kaitai::__private::KaitaiWriteStream>(&self, buf: &mut S) -> ::kaitai::error::Result<()> {
+                    #endian_load
+                    #(#write_statements)*
+                    Ok(())
+                }
+                #[allow(unused_variables)]
+                fn check_at(&self, path: &str, violations: &mut ::std::vec::Vec<::kaitai::error::Violation>) {
+                    #(#check_statements)*
+                }
+            }
+
             #[automatically_derived]
-            impl ::kaitai::KaitaiStruct for #id {
-                fn new<S: ::kaitai::__private::KaitaiStream>(buf: &mut S) -> ::kaitai::error::Result<Self> {
-                    #(#var_assignments);*;
-                    Ok(Self {
-                        #(#field_assignments),*
-                    })
+            impl ::kaitai::KaitaiVisit for #id #elided_lifetime {
+                fn type_name(&self) -> &'static str {
+                    #name
                 }
-                fn read<S: ::kaitai::__private::KaitaiStream>(&mut self, _: &mut S) -> ::kaitai::error::Result<()> {
-                    todo!();
+                #[allow(unused_variables)]
+                fn visit(&self, visitor: &mut dyn ::kaitai::visit::Visitor) {
+                    #(#visit_statements)*
                 }
             }
         });
@@ -13,6 +13,7 @@
 )]
 #![register_tool(tarpaulin)]
 
+mod args;
 mod de;
 mod error;
 mod hir;
@@ -31,7 +32,7 @@ pub fn kaitai_source(
     args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let ks_source_path = parse_macro_input!(args as syn::LitStr);
+    let args = parse_macro_input!(args as args::MacroArgs);
     let item_ast = parse_macro_input!(item as syn::Item);
 
     let struct_item = match item_ast {
@@ -53,16 +54,26 @@ pub fn kaitai_source(
     // // Span::call_site() is a nightly feature.
     let mut source_file_path = proc_macro::Span::call_site().source_file().path();
     source_file_path.pop();
-    let file_path = source_file_path.join(Path::new(&ks_source_path.value()));
+    let file_path = source_file_path.join(Path::new(&args.path.value()));
 
     let toml = std::fs::read_to_string(file_path).expect("error reading ksy file");
     let de_type = serde_yaml::from_str::<de::ty::Type>(&toml).expect("invalid ks file");
 
+    let opaque_types =
+        args.options.opaque_types || de_type.meta.as_ref().is_some_and(|m| m.ks_opaque_types);
     let inherited_meta = hir::ty::InheritedMeta {
-        id: Some((struct_item.ident, true)),
+        id: Some((struct_item.ident.clone(), true)),
         endianness: None,
+        options: args.options,
     };
 
-    let ty: hir::ty::Type = (inherited_meta, de_type).try_into().unwrap();
+    let mut ty: hir::ty::Type = (inherited_meta, de_type).try_into().unwrap();
+    ty.resolve_opaque(opaque_types, &args.opaque_types);
+    ty.resolve_borrowed();
+    ty.resolve_enum_reprs();
+    ty.resolve_endian_switches();
+    ty.check_switches();
+    ty.resolve_eq();
+    ty.forward_item(&struct_item);
     quote::ToTokens::into_token_stream(ty).into()
 }
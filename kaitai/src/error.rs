@@ -14,6 +14,10 @@ pub enum Error {
     #[error("end of stream reached, but no terminator {0} found")]
     EofBeforeTerminator(char),
 
+    /// Returned when writing a terminator that isn't a single byte, i.e. above `'\u{ff}'`.
+    #[error("terminator {0:?} isn't a single byte")]
+    InvalidTerminator(char),
+
     /// Returned by the `ensure_fixed_contents` function in `KaitaiStream` when the contents of the
     /// file don't match the expected value.
     #[error("unexpected fixed contents got {actual:?}, was expecting {expected:?}")]
@@ -28,7 +32,193 @@ pub enum Error {
     #[error("no matching enum variants found")]
     NoEnumMatch,
 
+    /// Returned when the value an attribute switches on matches none of its cases, and it has no
+    /// default `_` case.
+    #[error("no matching switch cases found")]
+    NoSwitchMatch,
+
+    /// Returned when the value the endianness of a type switches on matches none of its cases, and
+    /// it has no default `_` case.
+    #[error("unable to decide on the endianness of the type")]
+    UndecidedEndianness,
+
+    /// Returned when a string isn't valid UTF-8.
+    #[error(transparent)]
+    Utf8Error(#[from] std::str::Utf8Error),
+
+    /// Returned when an attribute doesn't satisfy its `valid` constraint while being read.
+    #[error("validation failed: {0}")]
+    ValidationFailed(Violation),
+
+    /// Returned by [`check`](crate::KaitaiWrite::check), and hence by
+    /// [`write`](crate::KaitaiWrite::write), when an instance is inconsistent with its format.
+    /// Contains every violation found.
+    #[error("consistency check failed: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    CheckFailed(Vec<Violation>),
+
+    /// Returned when parsing from a buffer that may not hold a whole instance yet, see
+    /// [`from_partial_bytes`](crate::KaitaiStruct::from_partial_bytes), and more bytes are needed.
+    #[error("incomplete input, at least {needed} more bytes needed")]
+    Incomplete {
+        /// A lower bound on the number of bytes still needed
+        needed: usize,
+    },
+
+    /// Returned when a byte array or string is larger than
+    /// [`max_alloc`](crate::ParseOptions::max_alloc). It is returned before the byte array or
+    /// string is allocated.
+    #[error("byte array of {size} bytes exceeds the allocation limit of {limit} bytes")]
+    AllocLimit {
+        /// The size of the byte array or string
+        size: u64,
+        /// The limit
+        limit: u64,
+    },
+
+    /// Returned when the byte arrays and strings read so far are larger, in total, than
+    /// [`max_total_bytes`](crate::ParseOptions::max_total_bytes).
+    #[error("byte arrays exceed the total limit of {limit} bytes")]
+    TotalBytesLimit {
+        /// The limit
+        limit: u64,
+    },
+
+    /// Returned when a repeated attribute has more elements than
+    /// [`max_repeat`](crate::ParseOptions::max_repeat).
+    #[error("{count} repetitions exceed the limit of {limit}")]
+    RepeatLimit {
+        /// The number of elements, or for `repeat: eos`, the number of elements read so far
+        count: u64,
+        /// The limit
+        limit: u64,
+    },
+
+    /// Returned when user defined types are nested deeper than
+    /// [`max_depth`](crate::ParseOptions::max_depth).
+    #[error("types nested deeper than the limit of {limit}")]
+    DepthLimit {
+        /// The limit
+        limit: u32,
+    },
+
+    /// Returned by generated parsers, giving where the underlying error occurred. The underlying
+    /// error is returned by [`root`](Error::root).
+    #[error("{path} ({ty}) at offset {offset}: {source}")]
+    Parse {
+        /// The path to the field that failed, e.g. `Root.chunks[3].header.version`
+        path: String,
+        /// The offset the stream reached when the field, or element of a repeated field, failed
+        offset: u64,
+        /// The type of the field, i.e. a built-in type such as `u2le`, `bytes` or `str`, or the
+        /// name of a user defined type or enum
+        ty: &'static str,
+        /// The underlying error
+        source: Box<Error>,
+    },
+
+    /// Returned when a [`ForwardStream`](crate::ForwardStream) is seeked further back than the
+    /// bytes it keeps, e.g. by a format that jumps back to an earlier position.
+    #[error(transparent)]
+    BackwardSeek(BackwardSeek),
+
     /// A generic IO error.
     #[error(transparent)]
-    IoError(#[from] std::io::Error),
+    IoError(std::io::Error),
+}
+
+impl Error {
+    /// Returns the underlying error, i.e. the error wrapped by [`Parse`](Error::Parse), or this
+    /// error if it isn't wrapped.
+    /// ```
+    /// # use kaitai::error::Error;
+    /// # fn handle(error: Error) {
+    /// if let Error::NoEnumMatch = error.root() {
+    ///     // ...
+    /// }
+    /// # }
+    /// ```
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Parse { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Returns the underlying error, like [`root`](Error::root), by value.
+    pub fn into_root(self) -> Error {
+        match self {
+            Error::Parse { source, .. } => source.into_root(),
+            _ => self,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        // `Seek` can only return an `io::Error`, so a `ForwardStream` wraps its error in one.
+        if let Some(MissingBytes(needed)) = e.get_ref().and_then(|e| e.downcast_ref()) {
+            return Error::Incomplete { needed: *needed };
+        }
+        match e.get_ref().and_then(|e| e.downcast_ref::<BackwardSeek>()) {
+            Some(seek) => Error::BackwardSeek(*seek),
+            None => Error::IoError(e),
+        }
+    }
+}
+
+/// The number of bytes a partial [`BytesStream`](crate::BytesStream) is missing. `Read` and `Seek`
+/// can only return an `io::Error`, so it is wrapped in one, and converted into
+/// [`Error::Incomplete`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("incomplete input, at least {0} more bytes needed")]
+pub(crate) struct MissingBytes(pub(crate) usize);
+
+/// A seek that a [`ForwardStream`](crate::ForwardStream) can't do, as the bytes at the target
+/// position were already dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("cannot seek back from {from} to {to} in a stream that can't seek")]
+pub struct BackwardSeek {
+    /// The position of the stream
+    pub from: u64,
+    /// The position that was seeked to
+    pub to: u64,
+}
+
+/// An invariant of a format that an instance doesn't uphold.
+///
+/// The path is the path of the attribute from the root of the instance, e.g. `chunks[3].data`.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum Violation {
+    /// The length of a byte array doesn't match its `size`.
+    #[error("{path}: expected {expected} bytes, found {actual}")]
+    Size {
+        /// The path of the attribute
+        path: String,
+        /// The `size` of the attribute
+        expected: u64,
+        /// The actual length of the attribute
+        actual: u64,
+    },
+
+    /// The number of elements of a repeated attribute doesn't match its `repeat-expr`.
+    #[error("{path}: expected {expected} elements, found {actual}")]
+    Count {
+        /// The path of the attribute
+        path: String,
+        /// The `repeat-expr` of the attribute
+        expected: u64,
+        /// The actual number of elements
+        actual: u64,
+    },
+
+    /// The value of an attribute doesn't satisfy its `valid` constraint.
+    #[error("{path}: {actual} is not {constraint}")]
+    Invalid {
+        /// The path of the attribute
+        path: String,
+        /// The actual value of the attribute
+        actual: String,
+        /// A description of the constraint, e.g. `== 2`
+        constraint: String,
+    },
 }
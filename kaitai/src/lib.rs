@@ -38,7 +38,102 @@
 //! The filepath provided to [`kaitai_source`] is taken relative to the current file, similarly to how
 //! modules are found. However, the filepath provided to [`from_file`](KaitaiStruct::from_file) is taken relative to the root
 //! of the project, like [`std::fs::File::open`].
-#![feature(extend_one, seek_stream_len)]
+//!
+//! Values missing from an enum are read as its `Unknown` variant, holding the raw value, like in
+//! other Kaitai Struct targets. The `strict_enums` option, e.g.
+//! `#[kaitai_source("example.ksy", strict_enums)]`, makes them fail with
+//! [`NoEnumMatch`](error::Error::NoEnumMatch) instead. Single attributes can override this with
+//! `-strict: true` or `-strict: false`.
+//!
+//! Enums list their variants in an `ALL` constant, and return their ids in the KSY file from
+//! `name`. They are displayed as, and parsed from, these ids with [`Display`](std::fmt::Display)
+//! and [`FromStr`](std::str::FromStr).
+//!
+//! An attribute with a `switch-on` type is held in an enum named after its type and itself, e.g.
+//! `ChunkBody` for the `body` of a `chunk`, with a variant for each type of its cases. Switches on
+//! enums must have a case for every variant, or a default `_` case. Values matching no case fail
+//! with [`NoSwitchMatch`](error::Error::NoSwitchMatch).
+//!
+//! Generated types derive `Debug`, `PartialEq` and `PartialOrd`, and `Eq` and `Ord` unless they
+//! contain floats. The attributes and visibility of the annotated struct are kept, and the traits
+//! it derives are derived for every generated type, e.g. `#[derive(Clone)]` makes the types it
+//! contains `Clone` as well. A type can derive other traits with a `-derive` key in its meta
+//! section, e.g. `-derive: [Clone, Hash]`, which replaces the traits of the annotated struct for
+//! the type and the types and enums inside it.
+//!
+//! The `endian` of a type can switch on an attribute of the type reading it through `_parent`,
+//! e.g. `switch-on: _parent.byte_order`, of the root type through `_root`, or of the type itself,
+//! e.g. `switch-on: byte_order`, in which case the attributes before it can't depend on the
+//! endianness. The types it contains inherit the endianness decided for it, which is returned by
+//! their `_is_le` method and kept when they are written. Values matching no case, and attributes
+//! of the root type not read yet, fail with
+//! [`UndecidedEndianness`](error::Error::UndecidedEndianness).
+//!
+//! Generated types also implement [`KaitaiVisit`], which passes their attributes to a
+//! [`Visitor`](visit::Visitor) along with their ids, docs and values, for tools that work with any
+//! format.
+//!
+//! # Borrowed mode
+//!
+//! By default, byte arrays and strings are copied out of the input into a [`Vec<u8>`] or a
+//! [`String`]. Passing the `borrowed` option to [`kaitai_source`] makes them borrow from the input
+//! instead, as a `&'a [u8]` or a `&'a str`. Types containing them then implement
+//! [`KaitaiBorrowedStruct`] rather than [`KaitaiStruct`].
+//! ```
+//! # use kaitai::{kaitai_source, KaitaiBorrowedStruct, error::Result};
+//! #[kaitai_source("../tests/formats/strings.ksy", borrowed)]
+//! struct Strings;
+//!
+//! fn main() -> Result<()> {
+//!     let bytes = b"\x05helloworld\0\x02\x01\x01\xaa\xbb\x02\xcc\xdd";
+//!     let strings: Strings<'_> = Strings::from_bytes(bytes)?;
+//!
+//!     assert_eq!(strings.name, "hello");
+//! #   Ok(())
+//! }
+//! ```
+//!
+//! # Debug mode
+//!
+//! With `ks-debug: true` in the meta section, or the `ks_debug` option, generated types record
+//! the offsets every attribute and element of a repeated attribute was read from. They are
+//! returned as [`DebugSpans`] by a `_debug` method, and given to [`Visitor`](visit::Visitor)s
+//! along with the attributes.
+//!
+//! # Opaque types
+//!
+//! With `ks-opaque-types: true` in the meta section, or the `opaque_types` option, types that
+//! aren't defined in the KSY file refer to Rust types implemented by hand, e.g. `type: png_chunk`
+//! refers to the `PngChunk` in scope. The option can also give their paths, e.g.
+//! `opaque_types(png_chunk = crate::png::Chunk)`. Without either, undefined types fail to compile.
+//!
+//! Opaque types implement [`KaitaiOpaque`], reading them from a stream and writing them back out,
+//! as well as `Debug`, `PartialEq` and `PartialOrd` like generated types, and the traits derived
+//! by the annotated struct. They may contain floats, so the types containing them don't derive
+//! `Eq` and `Ord`, unless the option says they implement them, e.g.
+//! `opaque_types(png_chunk = crate::png::Chunk: Eq, gif: Eq)`. Types containing opaque types
+//! can't be read asynchronously.
+//!
+//! # Untrusted input
+//!
+//! Sizes and counts read from the input are trusted, so a few bytes can make a parser allocate
+//! gigabytes. Input that isn't trusted should be parsed with
+//! [`from_bytes_with`](KaitaiStruct::from_bytes_with) or
+//! [`from_reader_with`](KaitaiStruct::from_reader_with), limiting allocations, repetitions and
+//! nesting with [`ParseOptions`].
+//!
+//! # Features
+//!
+//! - `mmap`: parse files by memory mapping them, through `MappedFile` and
+//!   `KaitaiStruct::from_file_mmap`. Only the parts of a file that are parsed are read from disk.
+//! - `async`: generated types get an `async fn new_async`, reading from an `AsyncKaitaiStream`,
+//!   i.e. anything implementing tokio's `AsyncRead` and `AsyncSeek`. Sockets can't seek, so they
+//!   should be wrapped in a [`ForwardStream`].
+//! - `serde`: generated types implement serde's `Serialize`, and `Deserialize` unless they borrow.
+//!   Byte arrays are represented as hexadecimal strings, or as base64 strings with the
+//!   `base64_bytes` option, and enums by the ids of their variants. Lazy attributes are
+//!   represented by where their elements start, and their count.
+#![feature(seek_stream_len)]
 #![deny(
     non_ascii_idents,
     missing_docs,
@@ -51,14 +146,34 @@
 )]
 
 pub mod error;
+pub mod visit;
 
 #[doc(inline)]
 pub use kaitai_macros::kaitai_source;
 
 mod runtime;
-pub use runtime::KaitaiStruct;
+#[cfg(feature = "async")]
+pub use runtime::AsyncKaitaiStream;
+#[cfg(feature = "mmap")]
+pub use runtime::MappedFile;
+pub use runtime::{
+    AttrSpans, BufferedStream, BytesStream, Consecutive, DebugSpans, ForwardStream,
+    KaitaiBorrowedStruct, KaitaiOpaque, KaitaiStream, KaitaiStruct, KaitaiWrite, KaitaiWriteStream,
+    LazyIter, LazyRepeat, ParseOptions, Partial,
+};
+pub use visit::KaitaiVisit;
 
 #[doc(hidden)]
 pub mod __private {
-    pub use crate::runtime::KaitaiStream;
+    #[cfg(feature = "serde")]
+    pub use crate::runtime::serde_repr;
+    #[cfg(feature = "async")]
+    pub use crate::runtime::AsyncKaitaiStream;
+    pub use crate::runtime::{
+        BytesStream, KaitaiStream, KaitaiWriteStream, ParseState, TerminatorFlags,
+    };
+    #[cfg(feature = "serde")]
+    pub use serde;
+    #[cfg(feature = "async")]
+    pub use tokio::io::AsyncSeekExt;
 }
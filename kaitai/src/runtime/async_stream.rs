@@ -0,0 +1,200 @@
+// The read functions mirror the ones in `stream.rs`, so that generated `new_async` functions can
+// read everything that generated `new` functions can.
+use crate::{
+    error::{Error, Result},
+    runtime::stream::{term_byte, TerminatorFlags},
+};
+
+use std::io::SeekFrom;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+/// A macro that generates functions to asynchronously read Kaitai Struct specified integers and
+/// convert them into Rust types.
+/// # Use
+/// ```ignore
+/// # trait Example: tokio::io::AsyncRead + Unpin {
+/// // s is the letter used by Kaitai Struct, [2, 4] are the numbers used by Kaitai Struct,
+/// // and [i32, i64] are the Rust types that the Kaitai Struct types (i.e. s2, s4) map to.
+/// generate_async_read_functions!(s; [2, 4] => [i32, i64]);
+/// # }
+/// ```
+macro_rules! generate_async_read_functions {
+    ($letter:ident; [$($size:literal),+$(,)?] => [$($rust_type:ty),+$(,)?]) => {
+        ::paste::paste! {
+        $(
+         #[doc = concat!(" Reads in a little endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), ")")]
+        async fn [<read_ $letter $size le>](&mut self) -> $crate::error::Result<$rust_type> {
+            self.[<read_ $rust_type _le>]().await.map_err(|e| e.into())
+        }
+        #[doc = concat!(" Reads in a big endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), ")")]
+        async fn [<read_ $letter $size be>](&mut self) -> $crate::error::Result<$rust_type> {
+            self.[<read_ $rust_type>]().await.map_err(|e| e.into())
+        }
+        )*
+    }
+    };
+}
+
+/// The asynchronous counterpart of [`KaitaiStream`](crate::runtime::KaitaiStream), implemented
+/// by all structs that implement [`AsyncRead`] and [`AsyncSeek`].
+///
+/// Streams that can't seek, such as sockets, can be wrapped in a
+/// [`ForwardStream`](crate::ForwardStream).
+// The trait is only implemented through the blanket implementation below, so whether the futures
+// are `Send` is known wherever the stream is concrete.
+#[allow(async_fn_in_trait)]
+pub trait AsyncKaitaiStream: AsyncRead + AsyncSeek + Unpin {
+    /// Returns whether the stream is at its end.
+    #[allow(clippy::wrong_self_convention)]
+    async fn is_eof(&mut self) -> Result<bool> {
+        let mut buf = [0u8; 1];
+        let n = self.read(&mut buf).await?;
+        if n == 0 {
+            Ok(true)
+        } else {
+            self.seek(SeekFrom::Current(-1)).await?;
+            Ok(false)
+        }
+    }
+
+    /// Returns the position in the stream.
+    async fn pos(&mut self) -> Result<u64> {
+        self.stream_position().await.map_err(|e| e.into())
+    }
+
+    /// Returns the size of the stream.
+    async fn size(&mut self) -> Result<u64> {
+        let pos = self.stream_position().await?;
+        let size = self.seek(SeekFrom::End(0)).await?;
+        self.seek(SeekFrom::Start(pos)).await?;
+        Ok(size)
+    }
+
+    /// Reads a number of bytes from the stream.
+    async fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>> {
+        let mut buffer = vec![0; count];
+        self.read_exact(&mut buffer).await?;
+        Ok(buffer)
+    }
+
+    /// Read the remaining bytes in the stream.
+    async fn read_bytes_full(&mut self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+
+    /// Read bytes up to a terminator. The flags have the same meaning as in
+    /// [`read_bytes_term`](crate::runtime::KaitaiStream::read_bytes_term).
+    async fn read_bytes_term(&mut self, term: char, flags: TerminatorFlags) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let term_byte = term_byte(term);
+
+        // Unlike the synchronous version, the bytes are read one at a time, as buffering is left
+        // to the stream (e.g. a `tokio::io::BufReader` or a `ForwardStream`).
+        loop {
+            let mut byte = [0u8; 1];
+            if self.read(&mut byte).await? == 0 {
+                return Err(Error::EofBeforeTerminator(term));
+            }
+
+            if Some(byte[0]) == term_byte {
+                if flags.include {
+                    buffer.push(byte[0]);
+                } else if !flags.consume {
+                    self.seek(SeekFrom::Current(-1)).await?;
+                }
+                return Ok(buffer);
+            }
+
+            buffer.push(byte[0]);
+        }
+    }
+
+    /// Ensures that the contents of the buffer is equal to the expected value.
+    async fn ensure_fixed_contents(&mut self, expected: &[u8]) -> Result<()> {
+        let actual = self.read_bytes(expected.len()).await?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedContents {
+                actual,
+                expected: Vec::from(expected),
+            })
+        }
+    }
+
+    /// Read in a u8 (KS: u1)
+    async fn read_u1(&mut self) -> Result<u8> {
+        self.read_u8().await.map_err(|e| e.into())
+    }
+
+    /// Read in an i8 (KS: s1)
+    async fn read_s1(&mut self) -> Result<i8> {
+        self.read_i8().await.map_err(|e| e.into())
+    }
+
+    generate_async_read_functions!(u; [2, 4, 8] => [u16, u32, u64]);
+    generate_async_read_functions!(s; [2, 4, 8] => [i16, i32, i64]);
+    generate_async_read_functions!(f; [4, 8] => [f32, f64]);
+}
+
+impl<T: AsyncRead + AsyncSeek + Unpin> AsyncKaitaiStream for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn new_buf() -> Cursor<Vec<u8>> {
+        Cursor::new(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])
+    }
+
+    #[tokio::test]
+    async fn is_eof() {
+        let mut buf = new_buf();
+
+        assert!(!buf.is_eof().await.unwrap());
+        buf.seek(SeekFrom::End(0)).await.unwrap();
+        assert!(buf.is_eof().await.unwrap());
+        assert_eq!(buf.pos().await.unwrap(), 10);
+        assert_eq!(buf.size().await.unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn read_bytes_term() {
+        let mut buf = new_buf();
+
+        let term = buf
+            .read_bytes_term('\u{3}', TerminatorFlags::consume())
+            .await
+            .unwrap();
+        assert_eq!(term, vec![0, 1, 2]);
+        let term = buf
+            .read_bytes_term('\u{6}', TerminatorFlags::new())
+            .await
+            .unwrap();
+        assert_eq!(term, vec![4, 5]);
+        let term = buf
+            .read_bytes_term('\u{7}', TerminatorFlags::include())
+            .await
+            .unwrap();
+        assert_eq!(term, vec![6, 7]);
+        assert!(buf
+            .read_bytes_term('\u{15}', TerminatorFlags::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn read_integers() {
+        let mut buf = new_buf();
+
+        assert_eq!(buf.read_u1().await.unwrap(), 0);
+        assert_eq!(buf.read_u2le().await.unwrap(), 0x02_01);
+        assert_eq!(buf.read_s4be().await.unwrap(), 0x03_04_05_06);
+        assert!(buf.ensure_fixed_contents(&[7, 8]).await.is_ok());
+        assert!(buf.read_u2be().await.is_err());
+    }
+}
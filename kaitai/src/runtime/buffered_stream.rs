@@ -0,0 +1,248 @@
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+/// The default capacity of a [`BufferedStream`], the same as that of a [`std::io::BufReader`].
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// A buffered stream over a reader.
+///
+/// Unlike a [`std::io::BufReader`], seeking to a position that is still in the buffer doesn't
+/// discard it, so that seeking back over the last few bytes doesn't cost a syscall.
+///
+/// [`from_file`](crate::KaitaiStruct::from_file) reads through a `BufferedStream`. Readers that
+/// aren't buffered have to be wrapped in one before being passed to
+/// [`from_reader`](crate::KaitaiStruct::from_reader).
+#[derive(Debug)]
+pub struct BufferedStream<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    /// The position of the stream in the buffer.
+    pos: usize,
+    /// The number of bytes in the buffer that were read from the reader.
+    filled: usize,
+    /// The position of the reader, i.e. the position of the end of the buffer. It is only queried
+    /// the first time it is needed.
+    inner_pos: Option<u64>,
+}
+
+impl<R: Read + Seek> BufferedStream<R> {
+    /// Creates a stream over `inner` with the default capacity.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Creates a stream over `inner` with the given capacity.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            inner_pos: None,
+        }
+    }
+
+    /// Returns a reference to the reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the reader, positioned where the stream was.
+    ///
+    /// The reader is read ahead of the stream, so it is seeked back over whatever is left in the
+    /// buffer.
+    pub fn into_inner(mut self) -> std::io::Result<R> {
+        let remaining = (self.filled - self.pos) as i64;
+        if remaining != 0 {
+            self.inner.seek(SeekFrom::Current(-remaining))?;
+        }
+        Ok(self.inner)
+    }
+
+    fn inner_pos(&mut self) -> std::io::Result<u64> {
+        match self.inner_pos {
+            Some(pos) => Ok(pos),
+            None => {
+                let pos = self.inner.stream_position()?;
+                self.inner_pos = Some(pos);
+                Ok(pos)
+            }
+        }
+    }
+
+    fn discard_buffer(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+}
+
+impl<R: Read + Seek> Read for BufferedStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Large reads bypass the buffer when it is empty, as copying through it gains nothing.
+        if self.pos == self.filled && buf.len() >= self.buf.len() {
+            self.discard_buffer();
+            let n = self.inner.read(buf)?;
+            if let Some(pos) = &mut self.inner_pos {
+                *pos += n as u64;
+            }
+            return Ok(n);
+        }
+
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> BufRead for BufferedStream<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos >= self.filled {
+            let n = self.inner.read(&mut self.buf)?;
+            if let Some(pos) = &mut self.inner_pos {
+                *pos += n as u64;
+            }
+            self.pos = 0;
+            self.filled = n;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+impl<R: Read + Seek> Seek for BufferedStream<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => {
+                let current = self.inner_pos()? - (self.filled - self.pos) as u64;
+                match current.checked_add_signed(n) {
+                    Some(target) => Some(target),
+                    None => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "invalid seek to a negative or overflowing position",
+                        ))
+                    }
+                }
+            }
+            SeekFrom::End(_) => None,
+        };
+
+        if let Some(target) = target {
+            let end = self.inner_pos()?;
+            let start = end - self.filled as u64;
+            if (start..=end).contains(&target) {
+                self.pos = (target - start) as usize;
+                return Ok(target);
+            }
+        }
+
+        let new_pos = match target {
+            Some(target) => self.inner.seek(SeekFrom::Start(target))?,
+            None => self.inner.seek(pos)?,
+        };
+        self.discard_buffer();
+        self.inner_pos = Some(new_pos);
+        Ok(new_pos)
+    }
+
+    fn stream_len(&mut self) -> std::io::Result<u64> {
+        // The default implementation seeks to the end, which would discard the buffer.
+        // NOTE: NIGHTLY FEATURE
+        self.inner.stream_len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{KaitaiStream, TerminatorFlags};
+    use std::io::Cursor;
+
+    /// A reader that counts the calls to `read` and `seek`.
+    #[derive(Default)]
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+        seeks: usize,
+    }
+
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    fn new_buf(capacity: usize) -> BufferedStream<Counting> {
+        BufferedStream::with_capacity(
+            capacity,
+            Counting {
+                inner: Cursor::new((0..20).collect()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn peeks_are_buffered() {
+        let mut buf = new_buf(32);
+
+        while !buf.is_eof().unwrap() {
+            buf.read_u1().unwrap();
+        }
+        assert_eq!(buf.pos().unwrap(), 20);
+        // One read fills the buffer and one finds the end of the reader.
+        assert_eq!(buf.get_ref().reads, 2);
+        // The position of the reader is only queried once.
+        assert_eq!(buf.get_ref().seeks, 1);
+    }
+
+    #[test]
+    fn read_bytes_term() {
+        let mut buf = new_buf(8);
+
+        let term = buf
+            .read_bytes_term('\u{b}', TerminatorFlags::new())
+            .unwrap();
+        assert_eq!(term, (0..11).collect::<Vec<u8>>());
+        assert_eq!(buf.read_u1().unwrap(), 11);
+        assert_eq!(buf.pos().unwrap(), 12);
+    }
+
+    #[test]
+    fn seek() {
+        let mut buf = new_buf(8);
+
+        assert_eq!(buf.seek(SeekFrom::Start(6)).unwrap(), 6);
+        assert_eq!(buf.read_u1().unwrap(), 6);
+        assert_eq!(buf.seek(SeekFrom::Current(-3)).unwrap(), 4);
+        assert_eq!(buf.read_u1().unwrap(), 4);
+        assert_eq!(buf.seek(SeekFrom::End(-2)).unwrap(), 18);
+        assert_eq!(buf.read_u1().unwrap(), 18);
+        assert!(buf.seek(SeekFrom::Current(-20)).is_err());
+        assert_eq!(buf.size().unwrap(), 20);
+        assert_eq!(buf.read_u1().unwrap(), 19);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut buf = new_buf(8);
+
+        buf.read_bytes(3).unwrap();
+        let mut inner = buf.into_inner().unwrap();
+        assert_eq!(inner.inner.position(), 3);
+        let mut byte = [0];
+        inner.read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [3]);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    error::{Error, MissingBytes, Result},
+    runtime::stream::{term_byte, TerminatorFlags},
+};
+
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+/// A stream over a slice of bytes that can lend out parts of the slice rather than copying them.
+///
+/// As it implements [`Read`] and [`Seek`], it is also a [`KaitaiStream`](crate::KaitaiStream).
+/// Reads copy straight out of the slice, and its position and size are plain field reads.
+#[derive(Clone, Debug)]
+pub struct BytesStream<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    partial: bool,
+}
+
+impl<'a> BytesStream<'a> {
+    /// Creates a stream positioned at the start of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            partial: false,
+        }
+    }
+
+    /// Creates a stream positioned at the start of `bytes`, which may be followed by more bytes
+    /// that haven't been received yet.
+    ///
+    /// Reading past the end of the bytes then fails with
+    /// [`Incomplete`](crate::error::Error::Incomplete), giving the number of bytes still needed,
+    /// rather than with an IO error. The end of the bytes isn't the end of the stream, so checking
+    /// for the end of the stream or its size also fails with `Incomplete`.
+    pub fn partial(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            partial: true,
+        }
+    }
+
+    /// Returns the part of the slice after the position of the stream.
+    fn remaining(&self) -> &'a [u8] {
+        // The position can be past the end of the slice after a seek.
+        self.bytes.get(self.pos..).unwrap_or_default()
+    }
+
+    /// The IO error returned when `needed` more bytes are needed than there are in the slice,
+    /// which is converted into [`Error::Incomplete`] for a partial slice.
+    fn eof(&self, needed: usize) -> std::io::Error {
+        if self.partial {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, MissingBytes(needed))
+        } else {
+            std::io::Error::from(std::io::ErrorKind::UnexpectedEof)
+        }
+    }
+
+    /// Reads a number of bytes from the stream without copying them.
+    pub fn read_bytes_ref(&mut self, count: usize) -> Result<&'a [u8]> {
+        let remaining = self.remaining();
+        if remaining.len() < count {
+            return Err(self.eof(count - remaining.len()).into());
+        }
+
+        self.pos += count;
+        Ok(&remaining[..count])
+    }
+
+    /// Read the remaining bytes in the stream without copying them.
+    pub fn read_bytes_full_ref(&mut self) -> Result<&'a [u8]> {
+        if self.partial {
+            return Err(self.eof(1).into());
+        }
+
+        let remaining = self.remaining();
+        self.pos += remaining.len();
+        Ok(remaining)
+    }
+
+    /// Read bytes up to a terminator without copying them.
+    ///
+    /// The flags have the same meaning as in
+    /// [`read_bytes_term`](crate::runtime::KaitaiStream::read_bytes_term).
+    pub fn read_bytes_term_ref(&mut self, term: char, flags: TerminatorFlags) -> Result<&'a [u8]> {
+        let remaining = self.remaining();
+        let index = match term_byte(term).and_then(|term| memchr::memchr(term, remaining)) {
+            Some(index) => index,
+            None if self.partial => return Err(self.eof(1).into()),
+            None => return Err(Error::EofBeforeTerminator(term)),
+        };
+
+        if flags.include {
+            self.pos += index + 1;
+            Ok(&remaining[..=index])
+        } else {
+            self.pos += if flags.consume { index + 1 } else { index };
+            Ok(&remaining[..index])
+        }
+    }
+}
+
+impl Read for BytesStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // The end of a partial slice isn't the end of the stream, so nothing can be read there.
+        if self.partial && self.remaining().is_empty() && !buf.is_empty() {
+            return Err(self.eof(1));
+        }
+        let n = self.remaining().read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        let remaining = self.remaining();
+        if remaining.len() < buf.len() {
+            return Err(self.eof(buf.len() - remaining.len()));
+        }
+
+        buf.copy_from_slice(&remaining[..buf.len()]);
+        self.pos += buf.len();
+        Ok(())
+    }
+}
+
+impl BufRead for BytesStream<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        // Like `read`, as the end of a partial slice isn't the end of the stream.
+        if self.partial && self.remaining().is_empty() {
+            return Err(self.eof(1));
+        }
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+impl Seek for BytesStream<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => (0, n as i64),
+            // The end of a partial slice isn't the end of the stream.
+            SeekFrom::End(_) if self.partial => return Err(self.eof(1)),
+            SeekFrom::End(n) => (self.bytes.len(), n),
+            SeekFrom::Current(n) => (self.pos, n),
+        };
+
+        match (base as i64).checked_add(offset) {
+            Some(pos) if pos >= 0 => {
+                self.pos = pos as usize;
+                Ok(pos as u64)
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.pos as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::KaitaiStream;
+
+    const BYTES: [u8; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+    #[test]
+    fn read_bytes_ref() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        assert_eq!(buf.read_bytes_ref(2).unwrap(), &[0, 1]);
+        assert_eq!(buf.read_bytes_ref(3).unwrap(), &[2, 3, 4]);
+        assert!(buf.read_bytes_ref(6).is_err());
+        assert_eq!(buf.pos().unwrap(), 5);
+    }
+
+    #[test]
+    fn read_bytes_full_ref() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        buf.seek(SeekFrom::Start(7)).unwrap();
+        assert_eq!(buf.read_bytes_full_ref().unwrap(), &[7, 8, 9]);
+        assert!(buf.is_eof().unwrap());
+    }
+
+    #[test]
+    fn read_bytes_term_ref() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        assert_eq!(
+            buf.read_bytes_term_ref('\u{3}', TerminatorFlags::consume())
+                .unwrap(),
+            &[0, 1, 2]
+        );
+        assert_eq!(
+            buf.read_bytes_term_ref('\u{6}', TerminatorFlags::new())
+                .unwrap(),
+            &[4, 5]
+        );
+        assert_eq!(
+            buf.read_bytes_term_ref('\u{7}', TerminatorFlags::all())
+                .unwrap(),
+            &[6, 7]
+        );
+        assert_eq!(
+            buf.read_bytes_term_ref('\u{8}', TerminatorFlags::include())
+                .unwrap(),
+            &[8]
+        );
+        assert!(buf
+            .read_bytes_term_ref('\u{15}', TerminatorFlags::new())
+            .is_err());
+    }
+
+    #[test]
+    fn read_integers() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        assert_eq!(buf.read_u1().unwrap(), 0);
+        assert_eq!(buf.read_u2le().unwrap(), 0x02_01);
+        assert_eq!(buf.read_s4be().unwrap(), 0x03_04_05_06);
+        assert_eq!(buf.pos().unwrap(), 7);
+        assert_eq!(buf.size().unwrap(), 10);
+        assert!(buf.read_u8be().is_err());
+        assert_eq!(buf.read_u2be().unwrap(), 0x07_08);
+        assert!(!buf.is_eof().unwrap());
+    }
+
+    #[test]
+    fn ensure_fixed_contents() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        assert!(buf.ensure_fixed_contents(&[0, 1, 2]).is_ok());
+        assert!(buf.ensure_fixed_contents(&[3, 5]).is_err());
+        assert_eq!(buf.read_bytes(3).unwrap(), vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn partial() {
+        let mut buf = BytesStream::partial(&BYTES);
+
+        assert_eq!(buf.read_u8le().unwrap(), 0x07_06_05_04_03_02_01_00);
+        assert!(matches!(
+            buf.read_u4le(),
+            Err(Error::Incomplete { needed: 2 })
+        ));
+        assert!(!buf.is_eof().unwrap());
+        assert_eq!(buf.read_bytes_ref(2).unwrap(), &[8, 9]);
+        assert!(matches!(buf.is_eof(), Err(Error::Incomplete { needed: 1 })));
+        assert!(matches!(buf.size(), Err(Error::Incomplete { .. })));
+        assert!(matches!(
+            buf.read_bytes_term_ref('\0', TerminatorFlags::new()),
+            Err(Error::Incomplete { .. })
+        ));
+    }
+
+    #[test]
+    fn seek() {
+        let mut buf = BytesStream::new(&BYTES);
+
+        assert_eq!(buf.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert_eq!(buf.read_u1().unwrap(), 8);
+        assert_eq!(buf.seek(SeekFrom::Current(-9)).unwrap(), 0);
+        assert!(buf.seek(SeekFrom::Current(-1)).is_err());
+        assert_eq!(buf.seek(SeekFrom::Start(20)).unwrap(), 20);
+        assert!(buf.is_eof().unwrap());
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+/// The offsets in the stream the attributes of an instance were read from.
+///
+/// They are recorded in debug mode, i.e. with `ks-debug: true` in the meta section of the ksy
+/// file, or with the `ks_debug` option of [`kaitai_source`](crate::kaitai_source), and returned by
+/// the `_debug` method of the generated types.
+///
+/// The spans don't take part in comparisons or hashing, so that instances with the same values
+/// are equal wherever they were read from.
+#[derive(Clone, Debug, Default)]
+pub struct DebugSpans {
+    attrs: Vec<AttrSpans>,
+}
+
+/// The offsets in the stream an attribute was read from, see [`DebugSpans`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AttrSpans {
+    /// The id of the attribute in the ksy file.
+    pub name: &'static str,
+    /// The offsets of the whole attribute.
+    pub span: Range<u64>,
+    /// The offsets of each element of a repeated attribute, or nothing.
+    pub elements: Vec<Range<u64>>,
+}
+
+impl DebugSpans {
+    /// Returns the offsets of the attribute `name`, or `None` if it wasn't read.
+    pub fn get(&self, name: &str) -> Option<&AttrSpans> {
+        self.attrs.iter().find(|a| a.name == name)
+    }
+
+    /// Returns the offsets of the whole attribute `name`.
+    pub fn span(&self, name: &str) -> Option<Range<u64>> {
+        self.get(name).map(|a| a.span.clone())
+    }
+
+    /// Returns the offsets of the element at `index` of the repeated attribute `name`.
+    pub fn element(&self, name: &str, index: usize) -> Option<Range<u64>> {
+        self.get(name).and_then(|a| a.elements.get(index).cloned())
+    }
+
+    /// Returns an iterator over the offsets of the attributes, in the order they were read.
+    pub fn iter(&self) -> std::slice::Iter<'_, AttrSpans> {
+        self.attrs.iter()
+    }
+
+    #[doc(hidden)]
+    pub fn push(&mut self, name: &'static str, span: Range<u64>, elements: Vec<Range<u64>>) {
+        self.attrs.push(AttrSpans {
+            name,
+            span,
+            elements,
+        });
+    }
+}
+
+impl PartialEq for DebugSpans {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DebugSpans {}
+
+impl PartialOrd for DebugSpans {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DebugSpans {
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for DebugSpans {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans() {
+        let mut spans = DebugSpans::default();
+        spans.push("header", 0..4, Vec::new());
+        spans.push("entries", 4..10, vec![4..7, 7..10]);
+
+        assert_eq!(spans.span("header"), Some(0..4));
+        assert_eq!(spans.span("entries"), Some(4..10));
+        assert_eq!(spans.element("entries", 1), Some(7..10));
+        assert_eq!(spans.element("entries", 2), None);
+        assert_eq!(spans.span("missing"), None);
+        assert_eq!(
+            spans.iter().map(|a| a.name).collect::<Vec<_>>(),
+            ["header", "entries"]
+        );
+        assert_eq!(spans, DebugSpans::default());
+    }
+}
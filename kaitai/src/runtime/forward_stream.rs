@@ -0,0 +1,356 @@
+use crate::error::BackwardSeek;
+
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+/// The default number of bytes a [`ForwardStream`] keeps behind its position.
+const DEFAULT_HISTORY: usize = 8 * 1024;
+
+/// The number of bytes a [`ForwardStream`] reads at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A stream over a reader that can't seek, such as a socket, a pipe or a decompressor.
+///
+/// The bytes read from the reader are buffered, so that the stream can seek forwards, and
+/// backwards over the last few bytes. Seeking further back than the bytes kept fails with a
+/// [`BackwardSeek`] error.
+///
+/// Seeking relative to the end of the stream, or querying its size, reads the reader to its end.
+///
+/// With the `async` feature, it also implements [`AsyncRead`](tokio::io::AsyncRead) and
+/// [`AsyncSeek`](tokio::io::AsyncSeek) when the reader implements `AsyncRead`, so that formats can
+/// be parsed from sockets with `new_async`.
+#[derive(Debug)]
+pub struct ForwardStream<R> {
+    inner: R,
+    /// The bytes read from the reader that are still kept, starting at `buf_start`.
+    buf: Vec<u8>,
+    buf_start: u64,
+    pos: u64,
+    /// The number of bytes kept behind the position.
+    history: usize,
+    /// Whether the end of the reader was reached.
+    eof: bool,
+    /// The seek started by `AsyncSeek::start_seek`.
+    #[cfg(feature = "async")]
+    pending_seek: Option<SeekFrom>,
+}
+
+impl<R> ForwardStream<R> {
+    /// Creates a stream over `inner` that keeps 8 KiB behind its position.
+    pub fn new(inner: R) -> Self {
+        Self::with_history(DEFAULT_HISTORY, inner)
+    }
+
+    /// Creates a stream over `inner` that keeps `history` bytes behind its position.
+    pub fn with_history(history: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            buf_start: 0,
+            pos: 0,
+            history,
+            eof: false,
+            #[cfg(feature = "async")]
+            pending_seek: None,
+        }
+    }
+
+    /// Returns a reference to the reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the reader. Any bytes that were read ahead of the position of the stream are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn buf_end(&self) -> u64 {
+        self.buf_start + self.buf.len() as u64
+    }
+
+    /// Drops the bytes that are further behind the position than the history.
+    fn compact(&mut self) {
+        let keep_from = self.pos.saturating_sub(self.history as u64);
+        if keep_from > self.buf_start {
+            let count = ((keep_from - self.buf_start) as usize).min(self.buf.len());
+            self.buf.drain(..count);
+            self.buf_start += count as u64;
+        }
+    }
+
+    /// Returns the buffered bytes at the position.
+    fn buffered(&self) -> &[u8] {
+        // The position can be past the end of the reader after a seek.
+        let start = ((self.pos - self.buf_start) as usize).min(self.buf.len());
+        &self.buf[start..]
+    }
+
+    /// Copies the buffered bytes at the position into `buf`, returning the number of bytes copied.
+    fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
+        let available = self.buffered();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n as u64;
+        n
+    }
+
+    /// Moves the position. The reader must have been read to its end if `pos` is relative to the
+    /// end.
+    fn seek_buffered(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+            SeekFrom::End(n) => self.buf_end().checked_add_signed(n),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if target < self.buf_start {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                BackwardSeek {
+                    from: self.pos,
+                    to: target,
+                },
+            ));
+        }
+
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+impl<R: Read> ForwardStream<R> {
+    /// Reads from the reader until `target` is buffered or the end of the reader is reached.
+    fn fill_to(&mut self, target: u64) -> std::io::Result<()> {
+        while !self.eof && self.buf_end() < target {
+            self.compact();
+
+            // After a seek far ahead, the bytes before the history are skipped rather than
+            // buffered. The buffer is empty at this point.
+            let keep_from = self.pos.saturating_sub(self.history as u64);
+            if self.buf_end() < keep_from {
+                let count = keep_from - self.buf_end();
+                let mut skipped = (&mut self.inner).take(count);
+                let n = std::io::copy(&mut skipped, &mut std::io::sink())?;
+                self.buf_start += n;
+                self.eof = n < count;
+                continue;
+            }
+
+            let len = self.buf.len();
+            self.buf.resize(len + CHUNK_SIZE, 0);
+            let result = self.inner.read(&mut self.buf[len..]);
+            let n = *result.as_ref().unwrap_or(&0);
+            self.buf.truncate(len + n);
+
+            match result {
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ForwardStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.buf_end() {
+            self.fill_to(self.pos + 1)?;
+        }
+        Ok(self.read_buffered(buf))
+    }
+}
+
+impl<R: Read> BufRead for ForwardStream<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos >= self.buf_end() {
+            self.fill_to(self.pos + 1)?;
+        }
+        Ok(self.buffered())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<R: Read> Seek for ForwardStream<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        if let SeekFrom::End(_) = pos {
+            self.fill_to(u64::MAX)?;
+        }
+        self.seek_buffered(pos)
+    }
+
+    fn stream_len(&mut self) -> std::io::Result<u64> {
+        // NOTE: NIGHTLY FEATURE
+        self.fill_to(u64::MAX)?;
+        Ok(self.buf_end())
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+#[cfg(feature = "async")]
+mod tokio_impls {
+    use super::*;
+
+    use std::{
+        pin::Pin,
+        task::{ready, Context, Poll},
+    };
+
+    use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+    impl<R: AsyncRead + Unpin> ForwardStream<R> {
+        /// The asynchronous counterpart of `fill_to`.
+        fn poll_fill_to(&mut self, cx: &mut Context<'_>, target: u64) -> Poll<std::io::Result<()>> {
+            while !self.eof && self.buf_end() < target {
+                // Unlike `fill_to`, bytes before the history are buffered and then dropped by the
+                // next compaction, rather than skipped.
+                self.compact();
+
+                let len = self.buf.len();
+                self.buf.resize(len + CHUNK_SIZE, 0);
+                let mut read_buf = ReadBuf::new(&mut self.buf[len..]);
+                let result = Pin::new(&mut self.inner).poll_read(cx, &mut read_buf);
+                let n = read_buf.filled().len();
+                self.buf.truncate(len + n);
+
+                match result {
+                    Poll::Ready(Ok(())) => self.eof = n == 0,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for ForwardStream<R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let this = self.get_mut();
+            if this.pos >= this.buf_end() {
+                ready!(this.poll_fill_to(cx, this.pos + 1))?;
+            }
+
+            let n = this.read_buffered(buf.initialize_unfilled());
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncSeek for ForwardStream<R> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+            self.get_mut().pending_seek = Some(position);
+            Ok(())
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+            let this = self.get_mut();
+            match this.pending_seek {
+                Some(pos) => {
+                    if let SeekFrom::End(_) = pos {
+                        ready!(this.poll_fill_to(cx, u64::MAX))?;
+                    }
+                    this.pending_seek = None;
+                    Poll::Ready(this.seek_buffered(pos))
+                }
+                None => Poll::Ready(Ok(this.pos)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::Error,
+        runtime::{KaitaiStream, TerminatorFlags},
+    };
+
+    const BYTES: [u8; 20] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    ];
+
+    // `&[u8]` implements `Read` but not `Seek`.
+    fn new_buf(history: usize) -> ForwardStream<&'static [u8]> {
+        ForwardStream::with_history(history, &BYTES)
+    }
+
+    #[test]
+    fn read() {
+        let mut buf = new_buf(4);
+
+        assert_eq!(buf.read_u2be().unwrap(), 0x00_01);
+        assert_eq!(buf.read_bytes(3).unwrap(), vec![2, 3, 4]);
+        while !buf.is_eof().unwrap() {
+            buf.read_u1().unwrap();
+        }
+        assert_eq!(buf.pos().unwrap(), 20);
+    }
+
+    #[test]
+    fn read_bytes_term() {
+        let mut buf = new_buf(4);
+
+        let term = buf
+            .read_bytes_term('\u{5}', TerminatorFlags::new())
+            .unwrap();
+        assert_eq!(term, vec![0, 1, 2, 3, 4]);
+        assert_eq!(buf.read_u1().unwrap(), 5);
+
+        // Nothing is read past the terminator, so no history is needed.
+        let mut buf = new_buf(0);
+        let term = buf
+            .read_bytes_term('\u{11}', TerminatorFlags::consume())
+            .unwrap();
+        assert_eq!(term, (0..17).collect::<Vec<u8>>());
+        assert_eq!(buf.read_u1().unwrap(), 18);
+    }
+
+    #[test]
+    fn seek() {
+        let mut buf = new_buf(4);
+
+        assert_eq!(buf.seek(SeekFrom::Start(10)).unwrap(), 10);
+        assert_eq!(buf.read_u1().unwrap(), 10);
+        assert_eq!(buf.seek(SeekFrom::Current(-4)).unwrap(), 7);
+        assert_eq!(buf.read_u1().unwrap(), 7);
+        assert_eq!(buf.seek(SeekFrom::End(-1)).unwrap(), 19);
+        assert_eq!(buf.size().unwrap(), 20);
+        assert_eq!(buf.read_u1().unwrap(), 19);
+        assert_eq!(buf.seek(SeekFrom::Start(30)).unwrap(), 30);
+        assert!(buf.is_eof().unwrap());
+    }
+
+    #[test]
+    fn backward_seek() {
+        let mut buf = new_buf(4);
+
+        buf.seek(SeekFrom::Start(12)).unwrap();
+        buf.read_u1().unwrap();
+
+        let err: Error = buf.seek(SeekFrom::Start(2)).unwrap_err().into();
+        assert!(matches!(
+            err,
+            Error::BackwardSeek(BackwardSeek { from: 13, to: 2 })
+        ));
+        assert_eq!(buf.read_u1().unwrap(), 13);
+    }
+}
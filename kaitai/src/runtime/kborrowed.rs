@@ -0,0 +1,55 @@
+use crate::{
+    error::Result,
+    runtime::{BytesStream, KaitaiStream, ParseOptions, ParseState},
+};
+
+use std::io::{Seek, SeekFrom};
+
+/// The trait that is implemented by the [kaitai_source](crate::kaitai_source) macro, in borrowed
+/// mode, for formats containing byte arrays or strings. Instead of copying them, these formats
+/// borrow their byte arrays and strings from the bytes they are created from.
+pub trait KaitaiBorrowedStruct<'a>
+where
+    Self: Sized,
+{
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, borrowing from it.
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        let mut b = BytesStream::new(bytes);
+        Self::new(&mut b)
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, borrowing from it,
+    /// within the limits given by `options`. See
+    /// [`KaitaiStruct::from_bytes_with`](crate::KaitaiStruct::from_bytes_with).
+    fn from_bytes_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self> {
+        let mut b = BytesStream::new(bytes);
+        ParseState::run(options, |state| Self::new_with(&mut b, state))
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, borrowing from it,
+    /// starting at `offset` rather than at the start of the array.
+    fn from_bytes_at(bytes: &'a [u8], offset: u64) -> Result<Self> {
+        let mut b = BytesStream::new(bytes);
+        b.seek(SeekFrom::Start(offset))?;
+        Self::new(&mut b)
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from the start of an array of bytes that may
+    /// not hold the whole instance yet, borrowing from it. See
+    /// [`KaitaiStruct::from_partial_bytes`](crate::KaitaiStruct::from_partial_bytes).
+    fn from_partial_bytes(bytes: &'a [u8]) -> Result<(Self, usize)> {
+        let mut b = BytesStream::partial(bytes);
+        let value = Self::new(&mut b)?;
+        Ok((value, b.pos()? as usize))
+    }
+
+    #[doc(hidden)]
+    fn new(stream: &mut BytesStream<'a>) -> Result<Self> {
+        ParseState::run(&ParseOptions::default(), |state| {
+            Self::new_with(stream, state)
+        })
+    }
+
+    #[doc(hidden)]
+    fn new_with(stream: &mut BytesStream<'a>, parse_state: &mut ParseState) -> Result<Self>;
+}
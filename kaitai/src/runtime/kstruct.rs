@@ -1,30 +1,230 @@
 // The contents of this file are **heavily** inspired by https://github.com/kaitai-io/kaitai_struct_rust_runtime.
 // Although this file is not a copy-paste, without their work this would have been much harder.
-use crate::{error::Result, runtime::KaitaiStream};
+use crate::{
+    error::Result,
+    runtime::{BufferedStream, BytesStream, KaitaiStream, ParseOptions, ParseState, Partial},
+};
+
+#[cfg(feature = "mmap")]
+use crate::runtime::MappedFile;
+
+use std::{
+    fmt::Debug,
+    io::{BufRead, Seek, SeekFrom},
+    marker::PhantomData,
+    path::Path,
+};
 
 /// The trait that is implemented by the [kaitai_source](crate::kaitai_source) macro.
 pub trait KaitaiStruct
 where
     Self: Sized,
 {
-    // TODO fix the documentation for this function and `from_bytes`. I can't find the correct
-    // terms to use.
-    /// Create an instance of a `KaitaiStruct` format from a file, relative to the root
-    /// of the project.
+    /// A partially created instance of the format, in which every field is an `Option`. See
+    /// [`Partial`].
+    type Partial: Debug + Default;
+
+    /// Create an instance of a `KaitaiStruct` format from the file at `path`. See
+    /// [`from_path`](KaitaiStruct::from_path).
     fn from_file(path: &str) -> Result<Self> {
-        let mut f = std::fs::File::open(path)?;
-        Self::new(&mut f)
+        Self::from_path(path)
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from the file at `path`.
+    ///
+    /// A relative path is resolved against the current working directory of the process, like
+    /// [`File::open`](std::fs::File::open) does. The file is read through a [`BufferedStream`].
+    fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let f = std::fs::File::open(path)?;
+        Self::new(&mut BufferedStream::new(f))
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from a file by memory mapping it, rather than
+    /// reading through it.
+    ///
+    /// Only the parts of the file that are parsed are read from disk. See [`MappedFile`] for the
+    /// caveats of memory mapping.
+    #[cfg(feature = "mmap")]
+    fn from_file_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = MappedFile::open(path)?;
+        Self::from_bytes(&file)
     }
 
     /// Create an instance of a `KaitaiStruct` format from an array of bytes.
+    ///
+    /// The bytes are read through a [`BytesStream`], which copies them straight out of the slice.
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let mut b = std::io::Cursor::new(bytes);
+        let mut b = BytesStream::new(bytes);
         Self::new(&mut b)
     }
 
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, within the limits
+    /// given by `options`. This should be used for untrusted input.
+    fn from_bytes_with(bytes: &[u8], options: &ParseOptions) -> Result<Self> {
+        let mut b = BytesStream::new(bytes);
+        ParseState::run(options, |state| Self::new_with(&mut b, state))
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, or if that fails,
+    /// as much of it as possible.
+    ///
+    /// This is meant for damaged files, e.g. truncated ones, to recover what precedes the damage.
+    /// The bytes are only parsed a second time, partially creating the instance, if parsing them
+    /// fails.
+    fn from_bytes_or_partial(bytes: &[u8]) -> std::result::Result<Self, Partial<Self>> {
+        Self::from_reader_or_partial(&mut BytesStream::new(bytes))
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from a reader, starting at its current
+    /// position, or if that fails, as much of it as possible. See
+    /// [`from_bytes_or_partial`](KaitaiStruct::from_bytes_or_partial).
+    fn from_reader_or_partial<R: BufRead + Seek>(
+        reader: &mut R,
+    ) -> std::result::Result<Self, Partial<Self>> {
+        let mut value = Self::Partial::default();
+        let start = match reader.pos() {
+            Ok(start) => start,
+            Err(error) => return Err(Partial { value, error }),
+        };
+        let error = match Self::new(reader) {
+            Ok(instance) => return Ok(instance),
+            Err(error) => error,
+        };
+        if reader.seek(SeekFrom::Start(start)).is_err() {
+            return Err(Partial { value, error });
+        }
+
+        let result = ParseState::run(&ParseOptions::default(), |state| {
+            Self::new_partial_with(reader, state, &mut value)
+        });
+        // The second parse fails in the same way, unless the reader changed in between.
+        Err(Partial {
+            value,
+            error: result.err().unwrap_or(error),
+        })
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from the start of an array of bytes that may
+    /// not hold the whole instance yet, e.g. the bytes received from a socket so far.
+    ///
+    /// Returns the instance and the number of bytes it took up. If there aren't enough bytes, this
+    /// fails with [`Incomplete`](crate::error::Error::Incomplete), giving a lower bound on the
+    /// number of bytes still needed. Parsing should then be restarted once more bytes have been
+    /// received, which is cheap as the bytes are indexed directly.
+    ///
+    /// Formats that read up to the end of the stream, e.g. with `repeat: eos`, are always
+    /// incomplete, as the end of the bytes isn't necessarily the end of the stream.
+    fn from_partial_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut b = BytesStream::partial(bytes);
+        let value = Self::new(&mut b)?;
+        Ok((value, b.pos()? as usize))
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from an array of bytes, starting at
+    /// `offset` rather than at the start of the array.
+    fn from_bytes_at(bytes: &[u8], offset: u64) -> Result<Self> {
+        let mut b = BytesStream::new(bytes);
+        b.seek(SeekFrom::Start(offset))?;
+        Self::new(&mut b)
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from a reader, starting at its current
+    /// position.
+    ///
+    /// Once the instance has been created, the reader is positioned after it. Hence, calling this
+    /// function repeatedly creates consecutive instances. See also
+    /// [`from_reader_iter`](KaitaiStruct::from_reader_iter).
+    ///
+    /// Readers that aren't buffered, such as a [`File`](std::fs::File), have to be wrapped in a
+    /// [`BufferedStream`].
+    fn from_reader<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
+        Self::new(reader)
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from a reader, starting at its current
+    /// position, within the limits given by `options`. This should be used for untrusted input.
+    fn from_reader_with<R: BufRead + Seek>(reader: &mut R, options: &ParseOptions) -> Result<Self> {
+        ParseState::run(options, |state| Self::new_with(reader, state))
+    }
+
+    /// Returns an iterator creating consecutive instances of a `KaitaiStruct` format from a
+    /// reader, starting at its current position, until the end of the reader is reached.
+    fn from_reader_iter<R: BufRead + Seek>(reader: &mut R) -> Consecutive<'_, Self, R> {
+        Consecutive {
+            reader,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
     #[doc(hidden)]
-    fn new<S: KaitaiStream>(stream: &mut S) -> Result<Self>;
+    fn new<S: KaitaiStream>(stream: &mut S) -> Result<Self> {
+        ParseState::run(&ParseOptions::default(), |state| {
+            Self::new_with(stream, state)
+        })
+    }
+
+    #[doc(hidden)]
+    fn new_with<S: KaitaiStream>(stream: &mut S, parse_state: &mut ParseState) -> Result<Self>;
+
+    #[doc(hidden)]
+    fn new_partial_with<S: KaitaiStream>(
+        stream: &mut S,
+        parse_state: &mut ParseState,
+        partial: &mut Self::Partial,
+    ) -> Result<()>;
+
+    /// Re-populate an existing instance of a `KaitaiStruct` format from a stream.
+    ///
+    /// The allocations of byte arrays, strings and repeated attributes are reused where possible,
+    /// making this cheaper than creating a new instance when parsing many instances in a row. If
+    /// an error is returned, the instance is left partially populated.
+    fn read<S: KaitaiStream>(&mut self, stream: &mut S) -> Result<()> {
+        ParseState::run(&ParseOptions::default(), |state| {
+            self.read_with(stream, state)
+        })
+    }
 
     #[doc(hidden)]
-    fn read<S: KaitaiStream>(&mut self, stream: &mut S) -> Result<()>;
+    fn read_with<S: KaitaiStream>(
+        &mut self,
+        stream: &mut S,
+        parse_state: &mut ParseState,
+    ) -> Result<()>;
+}
+
+/// An iterator creating consecutive instances of a `KaitaiStruct` format from a reader.
+///
+/// Returned by [`from_reader_iter`](KaitaiStruct::from_reader_iter). The iterator ends when the
+/// end of the reader is reached, or after the first error.
+#[derive(Debug)]
+pub struct Consecutive<'r, T, R> {
+    reader: &'r mut R,
+    done: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T, R: BufRead + Seek> Consecutive<'_, T, R> {
+    /// Returns the position in the reader, i.e. the position at which the next instance starts.
+    pub fn pos(&mut self) -> Result<u64> {
+        self.reader.pos()
+    }
+}
+
+impl<T: KaitaiStruct, R: BufRead + Seek> Iterator for Consecutive<'_, T, R> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.reader.is_eof() {
+            Ok(true) => None,
+            Ok(false) => Some(T::new(self.reader)),
+            Err(e) => Some(Err(e)),
+        };
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
 }
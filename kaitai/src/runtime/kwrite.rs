@@ -0,0 +1,64 @@
+use crate::{
+    error::{Error, Result, Violation},
+    runtime::KaitaiWriteStream,
+};
+
+use std::io::{Seek, Write};
+
+/// The trait that is implemented by the [kaitai_source](crate::kaitai_source) macro to write
+/// formats back out.
+pub trait KaitaiWrite {
+    /// Write an instance of a `KaitaiStruct` format to a stream.
+    ///
+    /// The instance is [checked](KaitaiWrite::check) first, and nothing is written if it is
+    /// inconsistent with its format.
+    fn write<W: Write + Seek>(&self, stream: &mut W) -> Result<()> {
+        self.check()?;
+        self.write_unchecked(stream)
+    }
+
+    /// Write an instance of a `KaitaiStruct` format to a stream without checking it first.
+    ///
+    /// Writing an inconsistent instance produces a stream that can't be read back in as the
+    /// same instance, if at all.
+    fn write_unchecked<W: Write + Seek>(&self, stream: &mut W) -> Result<()> {
+        self.write_to(stream)
+    }
+
+    /// Write an instance of a `KaitaiStruct` format to a file, relative to the root of the
+    /// project. The file is created if it doesn't exist and truncated if it does.
+    fn to_file(&self, path: &str) -> Result<()> {
+        self.check()?;
+        let mut f = std::fs::File::create(path)?;
+        self.write_unchecked(&mut f)
+    }
+
+    /// Write an instance of a `KaitaiStruct` format to an array of bytes.
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut b = std::io::Cursor::new(Vec::new());
+        self.write(&mut b)?;
+        Ok(b.into_inner())
+    }
+
+    /// Checks that an instance is consistent with its format, i.e. that byte arrays match their
+    /// `size`, repeated attributes match their `repeat-expr`, and attributes satisfy their `valid`
+    /// constraints. Every violation is returned in [`Error::CheckFailed`].
+    ///
+    /// Fixed contents aren't stored in the instance, and are always written as specified.
+    fn check(&self) -> Result<()> {
+        let mut violations = Vec::new();
+        self.check_at("", &mut violations);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::CheckFailed(violations))
+        }
+    }
+
+    #[doc(hidden)]
+    fn write_to<S: KaitaiWriteStream>(&self, stream: &mut S) -> Result<()>;
+
+    #[doc(hidden)]
+    fn check_at(&self, path: &str, violations: &mut Vec<Violation>);
+}
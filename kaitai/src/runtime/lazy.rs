@@ -0,0 +1,114 @@
+use crate::{
+    error::Result,
+    runtime::{KaitaiStream, KaitaiStruct},
+};
+
+use std::{io::SeekFrom, marker::PhantomData};
+
+/// A repeated attribute that is parsed lazily.
+///
+/// Repeated attributes of user defined types are lazy if they have `-lazy: true` in the ksy file,
+/// or if the `lazy` option is passed to [`kaitai_source`](crate::kaitai_source). Only the last
+/// attribute of a type can be lazy, as the attributes after it couldn't be found without parsing
+/// it.
+///
+/// Rather than storing the elements, it stores where they start in the stream, so that they can
+/// be parsed one at a time with [`iter`](LazyRepeat::iter), in constant memory. The stream is still
+/// moved past the elements when the instance containing them is created, so that what follows it
+/// is read from the right position: elements repeated until the end of the stream are skipped by
+/// seeking to the end, and a `repeat-expr` count of elements is parsed once without being kept. The elements are
+/// also neither written by [`KaitaiWrite`](crate::KaitaiWrite) nor checked by it, so they have
+/// to be written separately, after the instance containing them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound = "")
+)]
+pub struct LazyRepeat<T> {
+    start: u64,
+    count: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> LazyRepeat<T> {
+    #[doc(hidden)]
+    pub fn new(start: u64, count: Option<u64>) -> Self {
+        Self {
+            start,
+            count,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the position in the stream at which the elements start.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the number of elements if it is given by a `repeat-expr`, or `None` if the
+    /// elements repeat until the end of the stream.
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+}
+
+impl<T: KaitaiStruct> LazyRepeat<T> {
+    /// Returns an iterator parsing the elements from `stream`, which should be the stream the
+    /// instance containing them was created from.
+    ///
+    /// The iterator seeks to the start of the elements before parsing the first one, and ends
+    /// after the last element, or after the first error.
+    pub fn iter<'s, S: KaitaiStream>(&self, stream: &'s mut S) -> LazyIter<'s, T, S> {
+        LazyIter {
+            stream,
+            start: Some(self.start),
+            remaining: self.count,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator parsing the elements of a [`LazyRepeat`], returned by
+/// [`LazyRepeat::iter`].
+#[derive(Debug)]
+pub struct LazyIter<'s, T, S> {
+    stream: &'s mut S,
+    /// The position to seek to before parsing the first element.
+    start: Option<u64>,
+    remaining: Option<u64>,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: KaitaiStruct, S: KaitaiStream> LazyIter<'_, T, S> {
+    fn next_element(&mut self) -> Result<Option<T>> {
+        if let Some(start) = self.start.take() {
+            self.stream.seek(SeekFrom::Start(start))?;
+        }
+
+        match &mut self.remaining {
+            Some(0) => return Ok(None),
+            Some(remaining) => *remaining -= 1,
+            None if self.stream.is_eof()? => return Ok(None),
+            None => {}
+        }
+        T::new(self.stream).map(Some)
+    }
+}
+
+impl<T: KaitaiStruct, S: KaitaiStream> Iterator for LazyIter<'_, T, S> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_element().transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
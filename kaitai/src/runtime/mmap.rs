@@ -0,0 +1,44 @@
+use crate::error::Result;
+
+use std::{fs::File, ops::Deref, path::Path};
+
+use memmap2::Mmap;
+
+/// A read-only memory map of a file.
+///
+/// It dereferences to the bytes of the file, so formats can be created from it with `from_bytes`,
+/// including formats generated in borrowed mode, which then borrow from the map.
+/// [`KaitaiStruct::from_file_mmap`](crate::KaitaiStruct::from_file_mmap) is a shorthand for the
+/// former.
+///
+/// The file must not be modified while it is mapped, by this or any other process, as the bytes
+/// would change underneath the formats created from them.
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    /// Maps the file at `path`, relative to the root of the project.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: The caller is responsible for the file not being modified while it is mapped, as
+        // documented above. There is no way for this crate to enforce it.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.map
+    }
+}
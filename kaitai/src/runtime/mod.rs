@@ -1,7 +1,39 @@
 //! Module containing the traits implemented by the [`kaitai_source`](kaitai_macros::kaitai_source) macro.
 
+#[cfg(feature = "async")]
+mod async_stream;
+mod buffered_stream;
+mod bytes_stream;
+mod debug;
+mod forward_stream;
+mod kborrowed;
 mod kstruct;
+mod kwrite;
+mod lazy;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod opaque;
+mod parse_options;
+mod partial;
+#[cfg(feature = "serde")]
+pub mod serde_repr;
 mod stream;
+mod write_stream;
 
-pub use kstruct::KaitaiStruct;
-pub use stream::KaitaiStream;
+#[cfg(feature = "async")]
+pub use async_stream::AsyncKaitaiStream;
+pub use buffered_stream::BufferedStream;
+pub use bytes_stream::BytesStream;
+pub use debug::{AttrSpans, DebugSpans};
+pub use forward_stream::ForwardStream;
+pub use kborrowed::KaitaiBorrowedStruct;
+pub use kstruct::{Consecutive, KaitaiStruct};
+pub use kwrite::KaitaiWrite;
+pub use lazy::{LazyIter, LazyRepeat};
+#[cfg(feature = "mmap")]
+pub use mmap::MappedFile;
+pub use opaque::KaitaiOpaque;
+pub use parse_options::{ParseOptions, ParseState};
+pub use partial::Partial;
+pub use stream::{KaitaiStream, TerminatorFlags};
+pub use write_stream::KaitaiWriteStream;
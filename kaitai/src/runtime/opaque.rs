@@ -0,0 +1,75 @@
+use crate::{
+    error::{Result, Violation},
+    runtime::{KaitaiStream, KaitaiStruct, KaitaiWrite, KaitaiWriteStream, ParseState},
+};
+
+use std::fmt::Debug;
+
+/// A type implemented by hand, which generated types can contain as an opaque type, see
+/// [opaque types](crate#opaque-types).
+///
+/// [`KaitaiStruct`] and [`KaitaiWrite`] are implemented on top of it, so an opaque type can also
+/// be parsed and written on its own. Its [`Partial`](KaitaiStruct::Partial) is an `Option`, set
+/// once it is read in full, and it has no invariants to [`check`](KaitaiWrite::check).
+///
+/// # Examples
+///
+/// ```
+/// use kaitai::{error::Result, KaitaiOpaque, KaitaiStream, KaitaiWriteStream};
+///
+/// /// A string prefixed with its length.
+/// #[derive(Debug)]
+/// struct Pascal(String);
+///
+/// impl KaitaiOpaque for Pascal {
+///     fn read_opaque<S: KaitaiStream>(stream: &mut S) -> Result<Self> {
+///         let len = stream.read_u1()?;
+///         let bytes = stream.read_bytes(len as usize)?;
+///         Ok(Pascal(String::from_utf8(bytes).map_err(|e| e.utf8_error())?))
+///     }
+///
+///     fn write_opaque<S: KaitaiWriteStream>(&self, stream: &mut S) -> Result<()> {
+///         stream.write_u1(self.0.len() as u8)?;
+///         stream.write_bytes(self.0.as_bytes())
+///     }
+/// }
+/// ```
+pub trait KaitaiOpaque: Debug + Sized {
+    /// Reads an instance from `stream`, which is positioned at its start, leaving the stream
+    /// positioned at its end.
+    fn read_opaque<S: KaitaiStream>(stream: &mut S) -> Result<Self>;
+
+    /// Writes the instance out to `stream`, so that [`read_opaque`](KaitaiOpaque::read_opaque)
+    /// reads it back in.
+    fn write_opaque<S: KaitaiWriteStream>(&self, stream: &mut S) -> Result<()>;
+}
+
+impl<T: KaitaiOpaque> KaitaiStruct for T {
+    type Partial = Option<Self>;
+
+    fn new_with<S: KaitaiStream>(stream: &mut S, _: &mut ParseState) -> Result<Self> {
+        T::read_opaque(stream)
+    }
+
+    fn new_partial_with<S: KaitaiStream>(
+        stream: &mut S,
+        _: &mut ParseState,
+        partial: &mut Self::Partial,
+    ) -> Result<()> {
+        *partial = Some(T::read_opaque(stream)?);
+        Ok(())
+    }
+
+    fn read_with<S: KaitaiStream>(&mut self, stream: &mut S, _: &mut ParseState) -> Result<()> {
+        *self = T::read_opaque(stream)?;
+        Ok(())
+    }
+}
+
+impl<T: KaitaiOpaque> KaitaiWrite for T {
+    fn write_to<S: KaitaiWriteStream>(&self, stream: &mut S) -> Result<()> {
+        self.write_opaque(stream)
+    }
+
+    fn check_at(&self, _: &str, _: &mut Vec<Violation>) {}
+}
@@ -0,0 +1,390 @@
+use crate::{
+    error::{Error, Result},
+    runtime::KaitaiStream,
+};
+
+/// Limits on the resources used while parsing, for parsing untrusted input.
+///
+/// Every limit is `None`, i.e. unlimited, by default. Formats are created with these limits by
+/// [`from_bytes_with`](crate::KaitaiStruct::from_bytes_with) and
+/// [`from_reader_with`](crate::KaitaiStruct::from_reader_with).
+/// ```
+/// # use kaitai::ParseOptions;
+/// let options = ParseOptions {
+///     max_alloc: Some(1 << 20),
+///     max_depth: Some(32),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// The maximum size of a single byte array or string, in bytes. It is checked before the
+    /// byte array or string is allocated.
+    pub max_alloc: Option<u64>,
+    /// The maximum total size of all byte arrays and strings, in bytes.
+    pub max_total_bytes: Option<u64>,
+    /// The maximum number of elements of a repeated attribute.
+    pub max_repeat: Option<u64>,
+    /// The maximum nesting depth of user defined types, the outermost type being at depth 1.
+    pub max_depth: Option<u32>,
+}
+
+/// The state of a parse, tracking the resources used against the [`ParseOptions`], and the field
+/// being read for [`Error::Parse`].
+///
+/// It is passed through the functions generated by the [`kaitai_source`](crate::kaitai_source)
+/// macro.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct ParseState {
+    options: ParseOptions,
+    total_bytes: u64,
+    /// A frame for each user defined type being read, the outermost first. When an error is
+    /// returned, it is wrapped before the frames are left, so that it leads to the field that
+    /// failed.
+    frames: Vec<Frame>,
+    /// Whether the type being read is little endian, for types whose endianness is switched on
+    /// an attribute.
+    is_le: Option<bool>,
+    /// Whether the types whose endianness is switched on an attribute of the root type are little
+    /// endian, keyed by their names, as decided by the root type.
+    type_les: Vec<(&'static str, Option<bool>)>,
+}
+
+/// A user defined type being read.
+#[derive(Clone, Debug)]
+struct Frame {
+    ty: &'static str,
+    field: Option<Field>,
+    /// Whether the type reading the type is little endian, restored when leaving the type.
+    outer_le: Option<bool>,
+}
+
+/// The field of a user defined type being read.
+#[derive(Clone, Debug)]
+struct Field {
+    id: &'static str,
+    ty: &'static str,
+    /// The index of the element being read, if the field is repeated.
+    index: Option<u64>,
+}
+
+impl ParseState {
+    /// Creates the state of a parse with the given limits.
+    pub fn new(options: &ParseOptions) -> Self {
+        Self {
+            options: *options,
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the size of byte arrays and strings is limited, in which case they have to
+    /// be [`alloc`](ParseState::alloc)ed.
+    pub fn limits_alloc(&self) -> bool {
+        self.options.max_alloc.is_some() || self.options.max_total_bytes.is_some()
+    }
+
+    /// Accounts for a byte array or string of `size` bytes.
+    pub fn alloc(&mut self, size: u64) -> Result<()> {
+        if let Some(limit) = self.options.max_alloc {
+            if size > limit {
+                return Err(Error::AllocLimit { size, limit });
+            }
+        }
+
+        self.total_bytes = self.total_bytes.saturating_add(size);
+        match self.options.max_total_bytes {
+            Some(limit) if self.total_bytes > limit => Err(Error::TotalBytesLimit { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of elements of a repeated attribute.
+    pub fn repeat(&self, count: u64) -> Result<()> {
+        match self.options.max_repeat {
+            Some(limit) if count > limit => Err(Error::RepeatLimit { count, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enters the user defined type `ty`.
+    pub fn enter(&mut self, ty: &'static str) -> Result<()> {
+        if let Some(limit) = self.options.max_depth {
+            if self.frames.len() >= limit as usize {
+                return Err(Error::DepthLimit { limit });
+            }
+        }
+
+        self.frames.push(Frame {
+            ty,
+            field: None,
+            outer_le: self.is_le,
+        });
+        Ok(())
+    }
+
+    /// Leaves the user defined type being read, restoring the endianness of the type reading it.
+    pub fn exit(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.is_le = frame.outer_le;
+        }
+    }
+
+    /// Leaves the user defined type being read after reading it failed with `error`, like
+    /// [`exit`](ParseState::exit), wrapping the error first. `offset` is the position of the
+    /// stream, which is only asked for here so that reading fields doesn't have to keep it.
+    pub fn fail(&mut self, error: Error, offset: Result<u64>) -> Error {
+        let error = match offset {
+            Ok(offset) => self.wrap(error, offset),
+            Err(_) => error,
+        };
+        self.exit();
+        error
+    }
+
+    /// Reads the user defined type `ty` from `buf` with `read`, entering it before and leaving
+    /// it after, whether `read` succeeds or not.
+    pub fn read<S: KaitaiStream + ?Sized, T>(
+        &mut self,
+        ty: &'static str,
+        buf: &mut S,
+        read: impl FnOnce(&mut S, &mut Self) -> Result<T>,
+    ) -> Result<T> {
+        self.enter(ty)?;
+        match read(buf, self) {
+            Ok(value) => {
+                self.exit();
+                Ok(value)
+            }
+            Err(error) => Err(self.fail(error, buf.pos())),
+        }
+    }
+
+    /// Starts reading the field `id`, of type `ty`.
+    pub fn field(&mut self, id: &'static str, ty: &'static str) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.field = Some(Field {
+                id,
+                ty,
+                index: None,
+            });
+        }
+    }
+
+    /// Starts reading the element at `index` of the repeated field being read.
+    pub fn index(&mut self, index: u64) {
+        if let Some(Frame {
+            field: Some(field), ..
+        }) = self.frames.last_mut()
+        {
+            field.index = Some(index);
+        }
+    }
+
+    /// Returns whether the type being read is little endian, or [`Error::UndecidedEndianness`] if
+    /// the attribute switched on matched none of the cases of its endianness.
+    pub fn is_le(&self) -> Result<bool> {
+        self.is_le.ok_or(Error::UndecidedEndianness)
+    }
+
+    /// Sets whether the types read next are little endian, returning the previous value so it can
+    /// be restored once they are read.
+    pub fn set_le(&mut self, is_le: Option<bool>) -> Option<bool> {
+        std::mem::replace(&mut self.is_le, is_le)
+    }
+
+    /// Returns whether the type `ty` is little endian, as set by
+    /// [`set_type_le`](ParseState::set_type_le), or [`Error::UndecidedEndianness`] if the
+    /// attribute switched on matched none of the cases of its endianness, or wasn't read yet.
+    pub fn type_le(&self, ty: &'static str) -> Result<bool> {
+        self.type_les
+            .iter()
+            .find(|(t, _)| *t == ty)
+            .and_then(|(_, is_le)| *is_le)
+            .ok_or(Error::UndecidedEndianness)
+    }
+
+    /// Sets whether the type `ty` is little endian, once the attribute of the root type it
+    /// switches on is read.
+    pub fn set_type_le(&mut self, ty: &'static str, is_le: Option<bool>) {
+        match self.type_les.iter_mut().find(|(t, _)| *t == ty) {
+            Some((_, type_le)) => *type_le = is_le,
+            None => self.type_les.push((ty, is_le)),
+        }
+    }
+
+    /// Wraps an error returned while parsing in an [`Error::Parse`], giving the field that was
+    /// being read and the position `offset` the stream reached. [`Error::Incomplete`] isn't
+    /// wrapped, as it isn't a failure.
+    pub fn wrap(&self, error: Error, offset: u64) -> Error {
+        if let Error::Incomplete { .. } | Error::Parse { .. } = error {
+            return error;
+        }
+
+        let ty = match self.frames.iter().rev().find_map(|f| f.field.as_ref()) {
+            Some(field) => field.ty,
+            None => return error,
+        };
+
+        let mut path = self.frames[0].ty.to_owned();
+        for field in self.frames.iter().filter_map(|f| f.field.as_ref()) {
+            path.push('.');
+            path.push_str(field.id);
+            if let Some(index) = field.index {
+                path.push_str(&format!("[{}]", index));
+            }
+        }
+
+        Error::Parse {
+            path,
+            offset,
+            ty,
+            source: Box::new(error),
+        }
+    }
+
+    /// Runs `parse` with a new state with the given limits.
+    pub fn run<T>(options: &ParseOptions, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        parse(&mut Self::new(options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited() {
+        let mut state = ParseState::default();
+
+        assert!(!state.limits_alloc());
+        assert!(state.alloc(u64::MAX).is_ok());
+        assert!(state.alloc(u64::MAX).is_ok());
+        assert!(state.repeat(u64::MAX).is_ok());
+        for _ in 0..1000 {
+            assert!(state.enter("Example").is_ok());
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let mut state = ParseState::new(&ParseOptions {
+            max_alloc: Some(10),
+            max_total_bytes: Some(15),
+            max_repeat: Some(3),
+            max_depth: Some(2),
+        });
+
+        assert!(state.limits_alloc());
+        assert!(matches!(
+            state.alloc(11),
+            Err(Error::AllocLimit {
+                size: 11,
+                limit: 10
+            })
+        ));
+        assert!(state.alloc(10).is_ok());
+        assert!(matches!(
+            state.alloc(6),
+            Err(Error::TotalBytesLimit { limit: 15 })
+        ));
+
+        assert!(state.repeat(3).is_ok());
+        assert!(matches!(
+            state.repeat(4),
+            Err(Error::RepeatLimit { count: 4, limit: 3 })
+        ));
+
+        assert!(state.enter("Example").is_ok());
+        assert!(state.enter("Example").is_ok());
+        assert!(matches!(
+            state.enter("Example"),
+            Err(Error::DepthLimit { limit: 2 })
+        ));
+        state.exit();
+        state.exit();
+        assert!(state.enter("Example").is_ok());
+    }
+
+    #[test]
+    fn endianness() {
+        let mut state = ParseState::default();
+
+        assert!(matches!(state.is_le(), Err(Error::UndecidedEndianness)));
+        assert_eq!(state.set_le(Some(true)), None);
+        assert!(state.is_le().unwrap());
+        let outer = state.set_le(Some(false));
+        assert!(!state.is_le().unwrap());
+        state.set_le(outer);
+        assert!(state.is_le().unwrap());
+
+        assert!(matches!(
+            state.type_le("Example"),
+            Err(Error::UndecidedEndianness)
+        ));
+        state.set_type_le("Example", Some(false));
+        assert!(!state.type_le("Example").unwrap());
+        state.set_type_le("Example", None);
+        assert!(matches!(
+            state.type_le("Example"),
+            Err(Error::UndecidedEndianness)
+        ));
+    }
+
+    #[test]
+    fn wrap() {
+        let mut state = ParseState::default();
+
+        // Nothing has been read yet.
+        assert!(matches!(
+            state.wrap(Error::NoEnumMatch, 0),
+            Error::NoEnumMatch
+        ));
+
+        state.enter("Root").unwrap();
+        state.field("magic", "contents");
+        state.field("chunks", "Chunk");
+        state.index(3);
+        state.enter("Chunk").unwrap();
+        state.field("version", "u2le");
+
+        let error = state.wrap(Error::NoEnumMatch, 42);
+        assert_eq!(
+            error.to_string(),
+            "Root.chunks[3].version (u2le) at offset 42: no matching enum variants found"
+        );
+        assert!(matches!(error.root(), Error::NoEnumMatch));
+        // Wrapping twice doesn't nest the errors.
+        assert!(matches!(
+            state.wrap(error, 50),
+            Error::Parse { offset: 42, .. }
+        ));
+
+        assert!(matches!(
+            state.wrap(Error::Incomplete { needed: 1 }, 42),
+            Error::Incomplete { .. }
+        ));
+    }
+
+    #[test]
+    fn fail() {
+        let mut state = ParseState::default();
+        state.enter("Root").unwrap();
+        state.field("chunk", "Chunk");
+        state.set_le(Some(false));
+        state.enter("Chunk").unwrap();
+        state.set_le(Some(true));
+        state.field("version", "u2");
+
+        let error = state.fail(Error::UndecidedEndianness, Ok(7));
+        assert!(matches!(error, Error::Parse { offset: 7, .. }));
+        assert!(!state.is_le().unwrap());
+        let error = state.fail(error, Ok(9));
+        assert_eq!(
+            error.to_string(),
+            "Root.chunk.version (u2) at offset 7: unable to decide on the endianness of the type"
+        );
+        assert!(state.frames.is_empty());
+        assert!(state.is_le().is_err());
+    }
+}
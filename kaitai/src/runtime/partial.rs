@@ -0,0 +1,16 @@
+use crate::{error::Error, runtime::KaitaiStruct};
+
+/// A partially created instance of a `KaitaiStruct` format, along with the error that stopped it
+/// from being created.
+///
+/// Returned by [`from_bytes_or_partial`](KaitaiStruct::from_bytes_or_partial) and
+/// [`from_reader_or_partial`](KaitaiStruct::from_reader_or_partial). The fields of the partially
+/// created instance are `None` if they weren't read, and hold whatever was read before the error
+/// otherwise, e.g. the elements read so far of a repeated attribute.
+#[derive(Debug)]
+pub struct Partial<T: KaitaiStruct> {
+    /// The partially created instance
+    pub value: T::Partial,
+    /// The error that stopped the instance from being created
+    pub error: Error,
+}
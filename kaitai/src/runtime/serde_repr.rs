@@ -0,0 +1,306 @@
+//! The representations of generated types with serde, used by the code generated with the `serde`
+//! feature.
+//!
+//! Byte arrays are represented as hexadecimal strings, or as base64 strings with the
+//! `base64_bytes` option of [`kaitai_source`](crate::kaitai_source). Enums are represented by the
+//! ids of their variants, or by their values if they are missing from the enum.
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use std::marker::PhantomData;
+
+/// An encoding of byte arrays as strings.
+pub trait Encoding {
+    /// The name of the encoding, for errors.
+    const NAME: &'static str;
+
+    /// Encodes `bytes` as a string.
+    fn encode(bytes: &[u8]) -> String;
+
+    /// Decodes a string encoded by [`encode`](Encoding::encode), or returns `None` if it is
+    /// invalid.
+    fn decode(s: &str) -> Option<Vec<u8>>;
+}
+
+/// Lowercase hexadecimal, two digits per byte.
+#[derive(Clone, Copy, Debug)]
+pub struct Hex;
+
+impl Encoding for Hex {
+    const NAME: &'static str = "hexadecimal";
+
+    fn encode(bytes: &[u8]) -> String {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+        let mut s = String::with_capacity(bytes.len() * 2);
+        for b in bytes {
+            s.push(DIGITS[(b >> 4) as usize] as char);
+            s.push(DIGITS[(b & 0xf) as usize] as char);
+        }
+        s
+    }
+
+    fn decode(s: &str) -> Option<Vec<u8>> {
+        if !s.len().is_multiple_of(2) {
+            return None;
+        }
+        s.as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                let digit = |d: u8| (d as char).to_digit(16);
+                Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8)
+            })
+            .collect()
+    }
+}
+
+/// Standard base64, with padding.
+#[derive(Clone, Copy, Debug)]
+pub struct Base64;
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Encoding for Base64 {
+    const NAME: &'static str = "base64";
+
+    fn encode(bytes: &[u8]) -> String {
+        let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    s.push(BASE64_DIGITS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    s.push('=');
+                }
+            }
+        }
+        s
+    }
+
+    fn decode(s: &str) -> Option<Vec<u8>> {
+        if !s.len().is_multiple_of(4) {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+        for chunk in s.as_bytes().chunks(4) {
+            let padding = chunk.iter().rev().take_while(|&&d| d == b'=').count();
+            if padding > 2 {
+                return None;
+            }
+            let mut n = 0u32;
+            for (i, &d) in chunk[..4 - padding].iter().enumerate() {
+                let value = BASE64_DIGITS.iter().position(|&b| b == d)? as u32;
+                n |= value << (18 - 6 * i);
+            }
+            bytes.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+        }
+        Some(bytes)
+    }
+}
+
+/// Byte arrays that can be serialized as strings, including optional and repeated ones.
+pub trait SerializeBytes {
+    /// Serializes the byte arrays as strings in the encoding `E`.
+    fn serialize_bytes<E: Encoding, S: Serializer>(&self, serializer: S)
+        -> Result<S::Ok, S::Error>;
+}
+
+impl SerializeBytes for [u8] {
+    fn serialize_bytes<E: Encoding, S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&E::encode(self))
+    }
+}
+
+impl SerializeBytes for Vec<u8> {
+    fn serialize_bytes<E: Encoding, S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize_bytes::<E, S>(serializer)
+    }
+}
+
+impl<T: SerializeBytes + ?Sized> SerializeBytes for &T {
+    fn serialize_bytes<E: Encoding, S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        (**self).serialize_bytes::<E, S>(serializer)
+    }
+}
+
+impl<T: SerializeBytes> SerializeBytes for Option<T> {
+    fn serialize_bytes<E: Encoding, S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(bytes) => serializer.serialize_some(&Encoded::<E, T>(bytes, PhantomData)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<T: SerializeBytes> SerializeBytes for Vec<T> {
+    fn serialize_bytes<E: Encoding, S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|bytes| Encoded::<E, T>(bytes, PhantomData)))
+    }
+}
+
+struct Encoded<'a, E, T: ?Sized>(&'a T, PhantomData<E>);
+
+impl<E: Encoding, T: SerializeBytes + ?Sized> Serialize for Encoded<'_, E, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_bytes::<E, S>(serializer)
+    }
+}
+
+/// Byte arrays that can be deserialized from strings, including optional and repeated ones.
+pub trait DeserializeBytes: Sized {
+    /// Deserializes the byte arrays from strings in the encoding `E`.
+    fn deserialize_bytes<'de, E: Encoding, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error>;
+}
+
+impl DeserializeBytes for Vec<u8> {
+    fn deserialize_bytes<'de, E: Encoding, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        E::decode(&s).ok_or_else(|| {
+            de::Error::invalid_value(
+                de::Unexpected::Str(&s),
+                &format!("{} bytes", E::NAME).as_str(),
+            )
+        })
+    }
+}
+
+impl<T: DeserializeBytes> DeserializeBytes for Option<T> {
+    fn deserialize_bytes<'de, E: Encoding, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Ok(Option::<Decoded<E, T>>::deserialize(deserializer)?.map(|d| d.0))
+    }
+}
+
+impl<T: DeserializeBytes> DeserializeBytes for Vec<T> {
+    fn deserialize_bytes<'de, E: Encoding, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Ok(Vec::<Decoded<E, T>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|d| d.0)
+            .collect())
+    }
+}
+
+struct Decoded<E, T>(T, PhantomData<E>);
+
+impl<'de, E: Encoding, T: DeserializeBytes> Deserialize<'de> for Decoded<E, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self(
+            T::deserialize_bytes::<E, D>(deserializer)?,
+            PhantomData,
+        ))
+    }
+}
+
+/// Represents byte arrays as hexadecimal strings, with `#[serde(with = "...")]`.
+pub mod hex {
+    use super::*;
+
+    /// Serializes byte arrays as hexadecimal strings.
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: SerializeBytes + ?Sized,
+        S: Serializer,
+    {
+        bytes.serialize_bytes::<Hex, S>(serializer)
+    }
+
+    /// Deserializes byte arrays from hexadecimal strings.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: DeserializeBytes,
+        D: Deserializer<'de>,
+    {
+        T::deserialize_bytes::<Hex, D>(deserializer)
+    }
+}
+
+/// Represents byte arrays as base64 strings, with `#[serde(with = "...")]`.
+pub mod base64 {
+    use super::*;
+
+    /// Serializes byte arrays as base64 strings.
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: SerializeBytes + ?Sized,
+        S: Serializer,
+    {
+        bytes.serialize_bytes::<Base64, S>(serializer)
+    }
+
+    /// Deserializes byte arrays from base64 strings.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: DeserializeBytes,
+        D: Deserializer<'de>,
+    {
+        T::deserialize_bytes::<Base64, D>(deserializer)
+    }
+}
+
+/// The representation of an enum, by the id of a variant, or by a value.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EnumRepr {
+    /// The id of a variant, or a value as a string.
+    Name(String),
+    /// A value that fits in an `i64`.
+    Signed(i64),
+    /// A value that only fits in a `u64`.
+    Unsigned(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(Hex::encode(&[0x00, 0x4b, 0xff]), "004bff");
+        assert_eq!(Hex::decode("004BfF"), Some(vec![0x00, 0x4b, 0xff]));
+        assert_eq!(Hex::decode("004"), None);
+        assert_eq!(Hex::decode("0g"), None);
+    }
+
+    #[test]
+    fn base64() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob\xff", "Zm9vYv8="),
+        ];
+        for (bytes, s) in cases {
+            assert_eq!(Base64::encode(bytes), s);
+            assert_eq!(Base64::decode(s).as_deref(), Some(bytes));
+        }
+        assert_eq!(Base64::decode("Zm9"), None);
+        assert_eq!(Base64::decode("Z==="), None);
+        assert_eq!(Base64::decode("Zm-v"), None);
+    }
+}
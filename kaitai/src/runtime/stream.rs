@@ -2,7 +2,10 @@
 // Although this file is not a copy-paste, without their work this would have been much harder.
 use crate::error::{Error, Result};
 
-use std::io::{Read, Seek, SeekFrom};
+use std::{
+    convert::TryFrom,
+    io::{BufRead, Seek},
+};
 
 use byteorder::ReadBytesExt;
 
@@ -35,17 +38,29 @@ macro_rules! generate_read_functions {
     };
 }
 
+/// Returns the byte of the terminator `term`, or `None` if it is above `'\u{ff}'`, in which case
+/// no byte matches it.
+pub(crate) fn term_byte(term: char) -> Option<u8> {
+    u8::try_from(term).ok()
+}
+
+/// Flags determining how [`read_bytes_term`](KaitaiStream::read_bytes_term) treats the
+/// terminator.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub struct TerminatorFlags {
+    /// Whether the terminator is included in the bytes read.
     pub include: bool,
+    /// Whether the stream is moved past the terminator.
     pub consume: bool,
 }
 
 impl TerminatorFlags {
+    /// Neither includes nor consumes the terminator.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Includes the terminator.
     pub fn include() -> Self {
         Self {
             include: true,
@@ -53,6 +68,7 @@ impl TerminatorFlags {
         }
     }
 
+    /// Consumes the terminator.
     pub fn consume() -> Self {
         Self {
             include: false,
@@ -60,30 +76,43 @@ impl TerminatorFlags {
         }
     }
 
+    /// Includes and consumes the terminator.
     pub fn all() -> Self {
         Self {
             include: true,
             consume: true,
         }
     }
+
+    /// Returns `bytes` up to the first terminator, for byte arrays and strings with both a size
+    /// and a terminator, which are read in full and then truncated. The terminator is kept if
+    /// [`include`](TerminatorFlags::include) is set, and `bytes` are returned whole if they don't
+    /// contain it.
+    pub fn truncate(self, bytes: &[u8], term: char) -> &[u8] {
+        match term_byte(term).and_then(|term| memchr::memchr(term, bytes)) {
+            Some(index) if self.include => &bytes[..=index],
+            Some(index) => &bytes[..index],
+            None => bytes,
+        }
+    }
 }
 
-/// Trait that adds useful functions to all structs that implement Read and Seek.
-pub trait KaitaiStream: Read + Seek {
+/// Trait that adds useful functions to all structs that implement BufRead and Seek.
+///
+/// Looking ahead, to find a terminator or the end of the stream, is done in the buffer of the
+/// stream. Readers that aren't buffered, such as a [`File`](std::fs::File), can be wrapped in a
+/// [`BufferedStream`](crate::BufferedStream).
+pub trait KaitaiStream: BufRead + Seek {
     // The trait doesn't require a close method as buffers are automatically closed on drop.
     // The trait doesn't require a seek method as it is already implemented by std::io::Seek.
 
-    #[allow(missing_docs, clippy::wrong_self_convention)]
+    /// Returns whether the stream is at its end.
+    ///
+    /// This checks whether the buffer of the stream can be filled, rather than comparing the
+    /// position against the size.
+    #[allow(clippy::wrong_self_convention)]
     fn is_eof(&mut self) -> Result<bool> {
-        // TODO: benchmark against:
-        // let pos = self.pos()?;
-        // let size = self.seek(SeekFrom::End(0))?;
-        // self.seek(SeekFrom::Start(pos))?;
-        // Ok(pos >= size)
-        let mut buf = [0u8; 1];
-        let result = self.read(&mut buf).map(|n| n == 0);
-        self.seek(SeekFrom::Current(-1))?;
-        result.map_err(|e| e.into())
+        Ok(self.fill_buf()?.is_empty())
     }
 
     /// Returns the position in the stream.
@@ -111,6 +140,14 @@ pub trait KaitaiStream: Read + Seek {
         }
     }
 
+    /// Reads a number of bytes from the stream into `buffer`, replacing its contents but reusing
+    /// its allocation.
+    fn read_bytes_into(&mut self, buffer: &mut Vec<u8>, count: usize) -> Result<()> {
+        buffer.clear();
+        buffer.resize(count, 0);
+        self.read_exact(buffer).map_err(|e| e.into())
+    }
+
     /// Read the remaining bytes in the stream.
     fn read_bytes_full(&mut self) -> Result<Vec<u8>> {
         // TODO: benchmark against:
@@ -123,6 +160,14 @@ pub trait KaitaiStream: Read + Seek {
         }
     }
 
+    /// Read the remaining bytes in the stream into `buffer`, replacing its contents but reusing
+    /// its allocation.
+    fn read_bytes_full_into(&mut self, buffer: &mut Vec<u8>) -> Result<()> {
+        buffer.clear();
+        self.read_to_end(buffer)?;
+        Ok(())
+    }
+
     /// Read bytes up to a terminator.
     ///
     /// The Include flag determines whether the terminator is included in the return value. If the
@@ -130,29 +175,46 @@ pub trait KaitaiStream: Read + Seek {
     /// it points to the terminator.
     fn read_bytes_term(&mut self, term: char, flags: TerminatorFlags) -> Result<Vec<u8>> {
         let mut buffer = Vec::new();
+        self.read_bytes_term_into(&mut buffer, term, flags)?;
+        Ok(buffer)
+    }
 
+    /// Read bytes up to a terminator into `buffer`, replacing its contents but reusing its
+    /// allocation. The flags have the same meaning as in
+    /// [`read_bytes_term`](KaitaiStream::read_bytes_term).
+    fn read_bytes_term_into(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        term: char,
+        flags: TerminatorFlags,
+    ) -> Result<()> {
+        buffer.clear();
+        let term_byte = term_byte(term);
+
+        // The terminator is looked for in the buffer of the stream, which is only consumed up to
+        // it, so nothing is read past it.
         loop {
-            let mut temp_buffer = [0u8; 1];
-            let bytes_read = self.read(&mut temp_buffer)?;
+            let available = self.fill_buf()?;
 
-            if bytes_read == 0 {
+            if available.is_empty() {
                 return Err(Error::EofBeforeTerminator(term));
             }
 
-            if temp_buffer[0] as char == term {
-                if flags.include {
-                    // buffer.extend_from_slice(&temp_buffer);
-                    // NOTE: NIGHTLY FEATURE
-                    buffer.extend_one(temp_buffer[0]);
-                } else if !flags.consume {
-                    self.seek(SeekFrom::Current(-1))?;
-                }
-                return Ok(buffer);
+            if let Some(index) = term_byte.and_then(|term| memchr::memchr(term, available)) {
+                let end = if flags.include { index + 1 } else { index };
+                buffer.extend_from_slice(&available[..end]);
+                // The terminator is always consumed if it is included.
+                self.consume(if flags.include || flags.consume {
+                    index + 1
+                } else {
+                    index
+                });
+                return Ok(());
             }
 
-            // buffer.extend_from_slice(&temp_buffer);
-            // NOTE: NIGHTLY FEATURE
-            buffer.extend_one(temp_buffer[0]);
+            let len = available.len();
+            buffer.extend_from_slice(available);
+            self.consume(len);
         }
     }
 
@@ -192,12 +254,13 @@ pub trait KaitaiStream: Read + Seek {
     generate_read_functions!(f; [4, 8] => [f32, f64]);
 }
 
-impl<T: Read + Seek> KaitaiStream for T {}
+impl<T: BufRead + Seek> KaitaiStream for T {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use crate::runtime::BufferedStream;
+    use std::io::{Cursor, SeekFrom};
 
     fn new_buf() -> Cursor<Vec<u8>> {
         Cursor::new(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])
@@ -209,9 +272,11 @@ mod tests {
 
         buf.seek(SeekFrom::End(0)).unwrap();
         assert!(buf.is_eof().unwrap());
+        assert_eq!(buf.pos().unwrap(), 10);
 
         buf.seek(SeekFrom::Current(-3)).unwrap();
         assert!(!buf.is_eof().unwrap());
+        assert_eq!(buf.pos().unwrap(), 7);
     }
 
     #[test]
@@ -252,6 +317,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn truncate() {
+        let bytes = [b'a', b'b', 0, b'c', 0];
+
+        assert_eq!(TerminatorFlags::new().truncate(&bytes, '\0'), b"ab");
+        assert_eq!(TerminatorFlags::all().truncate(&bytes, '\0'), b"ab\0");
+        assert_eq!(TerminatorFlags::new().truncate(&bytes, 'd'), bytes);
+        // `'\u{100}'` isn't confused with the byte 0.
+        assert_eq!(TerminatorFlags::new().truncate(&bytes, '\u{100}'), bytes);
+    }
+
     #[test]
     fn read_bytes_term() {
         let mut buf: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
@@ -279,6 +355,67 @@ mod tests {
         assert!(buf
             .read_bytes_term('\u{15}', TerminatorFlags::new())
             .is_err());
+
+        buf.set_position(0);
+        assert!(matches!(
+            buf.read_bytes_term('\u{103}', TerminatorFlags::new()),
+            Err(Error::EofBeforeTerminator('\u{103}'))
+        ));
+    }
+
+    #[test]
+    fn read_bytes_term_buffered() {
+        // The terminator is found after the buffer has been filled a few times.
+        let mut bytes = vec![1; 30];
+        bytes[13] = 0;
+        let mut buf = BufferedStream::with_capacity(4, Cursor::new(bytes));
+
+        let term = buf.read_bytes_term('\0', TerminatorFlags::new()).unwrap();
+        assert_eq!(term, vec![1; 13]);
+        assert_eq!(buf.pos().unwrap(), 13);
+
+        let term = buf
+            .read_bytes_term('\0', TerminatorFlags::consume())
+            .unwrap();
+        assert!(term.is_empty());
+        assert_eq!(buf.pos().unwrap(), 14);
+        assert!(buf.read_bytes_term('\0', TerminatorFlags::new()).is_err());
+    }
+
+    #[test]
+    fn read_bytes_into() {
+        let mut buf = new_buf();
+        let mut buffer = Vec::with_capacity(8);
+        let ptr = buffer.as_ptr();
+
+        buf.read_bytes_into(&mut buffer, 2).unwrap();
+        assert_eq!(buffer, vec![0, 1]);
+        buf.read_bytes_into(&mut buffer, 3).unwrap();
+        assert_eq!(buffer, vec![2, 3, 4]);
+        assert_eq!(buffer.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn read_bytes_full_into() {
+        let mut buf = new_buf();
+        let mut buffer = vec![10, 11, 12];
+
+        buf.seek(SeekFrom::Start(8)).unwrap();
+        buf.read_bytes_full_into(&mut buffer).unwrap();
+        assert_eq!(buffer, vec![8, 9]);
+    }
+
+    #[test]
+    fn read_bytes_term_into() {
+        let mut buf = new_buf();
+        let mut buffer = vec![10, 11, 12];
+
+        buf.read_bytes_term_into(&mut buffer, '\u{3}', TerminatorFlags::all())
+            .unwrap();
+        assert_eq!(buffer, vec![0, 1, 2, 3]);
+        buf.read_bytes_term_into(&mut buffer, '\u{5}', TerminatorFlags::new())
+            .unwrap();
+        assert_eq!(buffer, vec![4]);
     }
 
     #[test]
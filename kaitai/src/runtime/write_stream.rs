@@ -0,0 +1,166 @@
+// The write functions mirror the read functions in `stream.rs`, so that every value read by a
+// `KaitaiStream` can be written back by a `KaitaiWriteStream`.
+use crate::{
+    error::{Error, Result},
+    runtime::stream::{term_byte, TerminatorFlags},
+};
+
+use std::io::{Seek, Write};
+
+use byteorder::WriteBytesExt;
+
+/// A macro that generates functions to write Rust types as Kaitai Struct specified integers.
+/// # Use
+/// ```ignore
+/// # trait Example: std::io::Write + std::io::Seek {
+/// // s is the letter used by Kaitai Struct, [2, 4] are the numbers used by Kaitai Struct,
+/// // and [i32, i64] are the Rust types that the Kaitai Struct types (i.e. s2, s4) map to.
+/// generate_write_functions!(s; [2, 4] => [i32, i64]);
+/// # }
+/// ```
+macro_rules! generate_write_functions {
+    ($letter:ident; [$($size:literal),+$(,)?] => [$($rust_type:ty),+$(,)?]) => {
+        ::paste::paste! {
+        $(
+         #[doc = concat!(" Writes out a little endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), ")")]
+        fn [<write_ $letter $size le>](&mut self, value: $rust_type) -> $crate::error::Result<()> {
+            use ::byteorder::WriteBytesExt;
+            self.[<write_ $rust_type>]::<::byteorder::LittleEndian>(value).map_err(|e| e.into())
+        }
+        #[doc = concat!(" Writes out a big endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), ")")]
+        fn [<write_ $letter $size be>](&mut self, value: $rust_type) -> $crate::error::Result<()> {
+            use ::byteorder::WriteBytesExt;
+            self.[<write_ $rust_type>]::<::byteorder::BigEndian>(value).map_err(|e| e.into())
+        }
+        )*
+    }
+    };
+}
+
+/// Trait that adds useful functions to all structs that implement Write and Seek.
+pub trait KaitaiWriteStream: Write + Seek {
+    /// Writes a number of bytes to the stream.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.write_all(bytes).map_err(|e| e.into())
+    }
+
+    /// Write bytes that were read up to a terminator.
+    ///
+    /// The flags must be the same as the ones used to read the bytes. If the Include flag is set,
+    /// the terminator is expected to already be part of `bytes`. Otherwise, the terminator is only
+    /// written if the Consumed flag is set, as an unconsumed terminator belongs to whatever is
+    /// read next. A terminator above `'\u{ff}'` can't be written, and returns
+    /// [`Error::InvalidTerminator`].
+    fn write_bytes_term(&mut self, bytes: &[u8], term: char, flags: TerminatorFlags) -> Result<()> {
+        self.write_bytes(bytes)?;
+        if !flags.include && flags.consume {
+            self.write_u8(term_byte(term).ok_or(Error::InvalidTerminator(term))?)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the contents that
+    /// [`ensure_fixed_contents`](crate::runtime::KaitaiStream::ensure_fixed_contents) expects to
+    /// read.
+    fn write_fixed_contents(&mut self, expected: &[u8]) -> Result<()> {
+        self.write_bytes(expected)
+    }
+
+    /// Write out a u8 (KS: u1)
+    fn write_u1(&mut self, value: u8) -> Result<()> {
+        self.write_u8(value).map_err(|e| e.into())
+    }
+
+    /// Write out an i8 (KS: s1)
+    fn write_s1(&mut self, value: i8) -> Result<()> {
+        self.write_i8(value).map_err(|e| e.into())
+    }
+
+    generate_write_functions!(u; [2, 4, 8] => [u16, u32, u64]);
+    generate_write_functions!(s; [2, 4, 8] => [i16, i32, i64]);
+    generate_write_functions!(f; [4, 8] => [f32, f64]);
+}
+
+impl<T: Write + Seek> KaitaiWriteStream for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn new_buf() -> Cursor<Vec<u8>> {
+        Cursor::new(Vec::new())
+    }
+
+    #[test]
+    fn write_bytes() {
+        let mut buf = new_buf();
+
+        buf.write_bytes(&[0, 1]).unwrap();
+        buf.write_bytes(&[2, 3, 4]).unwrap();
+        assert_eq!(buf.into_inner(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn write_bytes_term() {
+        let mut buf = new_buf();
+
+        buf.write_bytes_term(&[0, 1, 2], '\u{3}', TerminatorFlags::consume())
+            .unwrap();
+        buf.write_bytes_term(&[4, 5], '\u{6}', TerminatorFlags::new())
+            .unwrap();
+        buf.write_bytes_term(&[6, 7], '\u{7}', TerminatorFlags::all())
+            .unwrap();
+        buf.write_bytes_term(&[8], '\u{8}', TerminatorFlags::include())
+            .unwrap();
+        assert_eq!(buf.into_inner(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let mut buf = new_buf();
+        assert!(matches!(
+            buf.write_bytes_term(&[0], '\u{100}', TerminatorFlags::consume()),
+            Err(Error::InvalidTerminator('\u{100}'))
+        ));
+    }
+
+    #[test]
+    fn write_fixed_contents() {
+        let mut buf = new_buf();
+
+        buf.write_fixed_contents(b"glTF").unwrap();
+        assert_eq!(buf.into_inner(), b"glTF");
+    }
+
+    macro_rules! test_write_integer {
+        ($name:ident, $value:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let mut buf = new_buf();
+                buf.$name($value).unwrap();
+                assert_eq!(buf.into_inner(), $expected);
+            }
+        };
+    }
+
+    test_write_integer!(write_u1, 1, vec![1]);
+    test_write_integer!(write_s1, 1, vec![1]);
+
+    test_write_integer!(write_s2le, 513, vec![1, 2]);
+    test_write_integer!(write_s2be, 258, vec![1, 2]);
+    test_write_integer!(write_u2le, 513, vec![1, 2]);
+    test_write_integer!(write_u2be, 258, vec![1, 2]);
+
+    test_write_integer!(write_s4le, 67305985, vec![1, 2, 3, 4]);
+    test_write_integer!(write_s4be, 16909060, vec![1, 2, 3, 4]);
+    test_write_integer!(write_u4le, 67305985, vec![1, 2, 3, 4]);
+    test_write_integer!(write_u4be, 16909060, vec![1, 2, 3, 4]);
+
+    test_write_integer!(write_s8le, 578437695752307201, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    test_write_integer!(write_s8be, 72623859790382856, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    test_write_integer!(write_u8le, 578437695752307201, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    test_write_integer!(write_u8be, 72623859790382856, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+    test_write_integer!(write_f4le, 0.25, vec![0, 0, 128, 62]);
+    test_write_integer!(write_f4be, 0.25, vec![62, 128, 0, 0]);
+    test_write_integer!(write_f8le, 0.25, vec![0, 0, 0, 0, 0, 0, 208, 63]);
+    test_write_integer!(write_f8be, 0.25, vec![63, 208, 0, 0, 0, 0, 0, 0]);
+}
@@ -0,0 +1,115 @@
+//! Walking the attributes of parsed formats without knowing their types.
+//!
+//! Every type generated by [`kaitai_source`](crate::kaitai_source) implements [`KaitaiVisit`],
+//! which passes each of its attributes, in order, to a [`Visitor`]. The values of the attributes
+//! are given as [`Value`]s, so that tools such as dumpers and hex viewers can be written once for
+//! every format.
+//!
+//! # Examples
+//!
+//! ```
+//! use kaitai::visit::{Field, KaitaiVisit, Value};
+//!
+//! fn dump(instance: &dyn KaitaiVisit, depth: usize) {
+//!     instance.visit(&mut |field: Field<'_>| {
+//!         print!("{:indent$}{}: ", "", field.name, indent = depth * 2);
+//!         match field.value {
+//!             Value::Struct(inner) => {
+//!                 println!("{}", inner.type_name());
+//!                 dump(inner, depth + 1);
+//!             }
+//!             value => println!("{:?}", value),
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::{fmt::Debug, ops::Range};
+
+/// The trait that is implemented by the [kaitai_source](crate::kaitai_source) macro to expose the
+/// attributes of a type.
+pub trait KaitaiVisit: Debug {
+    /// The name of the generated type, e.g. `Chunk` for a `chunk` type.
+    fn type_name(&self) -> &'static str;
+
+    /// Pass every attribute of the instance to `visitor`, in the order they were parsed.
+    ///
+    /// Attributes of user defined types are passed as [`Value::Struct`]; their own attributes
+    /// are only visited if the visitor calls `visit` on them.
+    fn visit(&self, visitor: &mut dyn Visitor);
+}
+
+/// A receiver of the attributes of a [`KaitaiVisit`] instance.
+///
+/// It is implemented for every closure taking a [`Field`].
+pub trait Visitor {
+    /// Visit an attribute.
+    fn visit_field(&mut self, field: Field<'_>);
+}
+
+impl<F> Visitor for F
+where
+    F: FnMut(Field<'_>),
+{
+    fn visit_field(&mut self, field: Field<'_>) {
+        self(field)
+    }
+}
+
+/// An attribute of a [`KaitaiVisit`] instance.
+#[derive(Debug)]
+pub struct Field<'a> {
+    /// The id of the attribute in the ksy file.
+    pub name: &'static str,
+    /// The documentation of the attribute in the ksy file, or an empty string.
+    pub doc: &'static str,
+    /// The offsets in the stream the attribute was read from, if they were recorded in debug
+    /// mode, see [`DebugSpans`](crate::DebugSpans).
+    pub span: Option<Range<u64>>,
+    /// The value of the attribute.
+    pub value: Value<'a>,
+}
+
+/// The value of an attribute, or of an element of a repeated attribute.
+#[derive(Debug)]
+pub enum Value<'a> {
+    /// An unsigned integer.
+    Unsigned(u64),
+    /// A signed integer.
+    Signed(i64),
+    /// A floating point number.
+    Float(f64),
+    /// A byte array.
+    Bytes(&'a [u8]),
+    /// A string.
+    Str(&'a str),
+    /// A value of an enum.
+    Enum {
+        /// The id of the variant in the ksy file, or `None` if the value isn't in the enum.
+        name: Option<&'static str>,
+        /// The integer value.
+        value: i128,
+    },
+    /// An instance of a user defined type.
+    Struct(&'a dyn KaitaiVisit),
+    /// An instance of an opaque type, i.e. a type defined outside the ksy file.
+    Opaque(&'a dyn Debug),
+    /// The elements of a repeated attribute.
+    Array(Vec<Element<'a>>),
+    /// The elements of a [`LazyRepeat`](crate::LazyRepeat), which aren't stored in the instance.
+    Lazy {
+        /// The offset of the first element in the stream.
+        start: u64,
+        /// The number of elements, if it is known.
+        count: Option<u64>,
+    },
+}
+
+/// An element of a repeated attribute.
+#[derive(Debug)]
+pub struct Element<'a> {
+    /// The offsets in the stream the element was read from, if they were recorded.
+    pub span: Option<Range<u64>>,
+    /// The value of the element.
+    pub value: Value<'a>,
+}
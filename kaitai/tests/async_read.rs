@@ -0,0 +1,50 @@
+#![cfg(feature = "async")]
+
+use kaitai::{kaitai_source, ForwardStream, KaitaiStruct};
+
+use tokio::io::AsyncWriteExt;
+
+#[kaitai_source("formats/basic_be.ksy")]
+struct BasicBigEndian;
+
+#[kaitai_source("formats/user_type_be.ksy")]
+struct UserType;
+
+#[kaitai_source("formats/repeat.ksy")]
+struct Repeat;
+
+#[tokio::test]
+async fn new_async() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+
+    let mut buf = std::io::Cursor::new(&input);
+    let file = BasicBigEndian::new_async(&mut buf).await.unwrap();
+    assert_eq!(file, BasicBigEndian::from_bytes(&input).unwrap());
+
+    let mut buf = std::io::Cursor::new(&input);
+    let file = UserType::new_async(&mut buf).await.unwrap();
+    assert_eq!(file, UserType::from_bytes(&input).unwrap());
+}
+
+#[tokio::test]
+async fn from_duplex() {
+    let mut input = vec![0xde, 0xad];
+    input.extend(0..24);
+    let (mut writer, reader) = tokio::io::duplex(16);
+
+    // The duplex stream only holds 16 bytes, so the parser has to wait for the writer.
+    let expected = input.clone();
+    let write = tokio::spawn(async move {
+        for chunk in expected.chunks(7) {
+            writer.write_all(chunk).await.unwrap();
+        }
+    });
+    let parse = tokio::spawn(async move {
+        let mut buf = ForwardStream::new(reader);
+        Repeat::new_async(&mut buf).await
+    });
+
+    write.await.unwrap();
+    let file = parse.await.unwrap().unwrap();
+    assert_eq!(file, Repeat::from_bytes(&input).unwrap());
+}
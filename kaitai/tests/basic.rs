@@ -1,4 +1,6 @@
-use kaitai::{kaitai_source, KaitaiStruct};
+use kaitai::{error::Error, kaitai_source, ForwardStream, KaitaiStruct, KaitaiWrite};
+
+use std::io::{Cursor, Seek, SeekFrom};
 
 #[kaitai_source("formats/basic_be.ksy")]
 struct BasicBigEndian;
@@ -30,3 +32,109 @@ fn other_attributes_untouched() {
     let file = BasicLittleEndian::from_file("tests/files/example.basic").unwrap();
     println!("{:#?}", file);
 }
+
+#[test]
+fn basic_round_trip() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+
+    let file = BasicBigEndian::from_bytes(&input).unwrap();
+    assert_eq!(file.to_bytes().unwrap(), &input[..14]);
+
+    let file = BasicLittleEndian::from_bytes(&input).unwrap();
+    assert_eq!(file.to_bytes().unwrap(), &input[..14]);
+}
+
+#[test]
+fn from_path() {
+    let path = std::path::Path::new("tests/files/example.basic");
+    let file = BasicBigEndian::from_path(path).unwrap();
+    assert_eq!(file.header, 0x50_4b);
+}
+
+#[test]
+fn from_reader() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+    let mut reader = Cursor::new(&input);
+    reader.seek(SeekFrom::Start(14)).unwrap();
+
+    let file = BasicBigEndian::from_reader(&mut reader).unwrap();
+    assert_eq!(file, BasicBigEndian::from_bytes(&input[14..]).unwrap());
+    assert_eq!(reader.position(), 28);
+}
+
+#[test]
+fn from_bytes_at() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+
+    let file = BasicBigEndian::from_bytes_at(&input, 14).unwrap();
+    assert_eq!(file, BasicBigEndian::from_bytes(&input[14..]).unwrap());
+    assert!(BasicBigEndian::from_bytes_at(&input, input.len() as u64).is_err());
+}
+
+#[test]
+fn from_reader_iter() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+    let mut reader = Cursor::new(&input[..42]);
+
+    let mut iter = BasicBigEndian::from_reader_iter(&mut reader);
+    for i in 0..3 {
+        assert_eq!(iter.pos().unwrap(), i * 14);
+        let file = iter.next().unwrap().unwrap();
+        assert_eq!(file, BasicBigEndian::from_bytes_at(&input, i * 14).unwrap());
+    }
+    assert!(iter.next().is_none());
+
+    // Parsing stops after the first error.
+    let mut reader = Cursor::new(&input[..20]);
+    let mut iter = BasicBigEndian::from_reader_iter(&mut reader);
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn from_non_seekable_reader() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+    // `&[u8]` implements `Read` but not `Seek`.
+    let mut reader = ForwardStream::new(&input[..42]);
+
+    let files = BasicBigEndian::from_reader_iter(&mut reader)
+        .collect::<kaitai::error::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[2], BasicBigEndian::from_bytes_at(&input, 28).unwrap());
+}
+
+#[test]
+fn from_partial_bytes() {
+    let input = std::fs::read("tests/files/example.basic").unwrap();
+
+    // A frame decoder receiving the bytes in chunks of 5.
+    let mut received = Vec::new();
+    let mut files = Vec::new();
+    for chunk in input[..28].chunks(5) {
+        received.extend_from_slice(chunk);
+        loop {
+            match BasicBigEndian::from_partial_bytes(&received) {
+                Ok((file, consumed)) => {
+                    assert_eq!(consumed, 14);
+                    received.drain(..consumed);
+                    files.push(file);
+                }
+                Err(Error::Incomplete { needed }) => {
+                    assert!(needed > 0);
+                    break;
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[1], BasicBigEndian::from_bytes_at(&input, 14).unwrap());
+    assert!(received.is_empty());
+    assert!(matches!(
+        BasicBigEndian::from_partial_bytes(&input[..8]),
+        Err(Error::Incomplete { needed: 2 })
+    ));
+}
@@ -0,0 +1,179 @@
+use kaitai::{
+    error::{Error, Violation},
+    kaitai_source, KaitaiStruct, KaitaiWrite,
+};
+
+#[kaitai_source("formats/check.ksy")]
+struct Check;
+
+#[kaitai_source("formats/valid.ksy")]
+struct Valid;
+
+fn input() -> Vec<u8> {
+    vec![
+        2, 2, // version, num_entries
+        1, 5, 0, 0xab, 0xcd, // entries[0]
+        3, 10, 0, 0xef, 0x01, // entries[1]
+        3, 0, 0x11, 0x22, 0x33, // len_tail, tail
+    ]
+}
+
+#[test]
+fn check_ok() {
+    let result = Check::from_bytes(&input()).unwrap();
+
+    assert_eq!(result.entries.len(), 2);
+    assert_eq!(result.entries[1].level, 10);
+    assert_eq!(result.tail, vec![0x11, 0x22, 0x33]);
+    assert!(result.check().is_ok());
+    assert_eq!(result.to_bytes().unwrap(), input());
+}
+
+#[test]
+fn read_invalid() {
+    let mut input = input();
+    input[0] = 3;
+
+    match Check::from_bytes(&input).map_err(Error::into_root) {
+        Err(Error::ValidationFailed(Violation::Invalid { path, .. })) => {
+            assert_eq!(path, "version")
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    let mut input = self::input();
+    input[8] = 11;
+
+    assert!(matches!(
+        Check::from_bytes(&input).map_err(Error::into_root),
+        Err(Error::ValidationFailed(_))
+    ));
+}
+
+#[test]
+fn check_violations() {
+    let mut result = Check::from_bytes(&input()).unwrap();
+    result.version = 1;
+    result.entries[0].kind = 4;
+    result.entries[1].magic.push(0);
+    result.tail.pop();
+
+    let violations = match result.check() {
+        Err(Error::CheckFailed(violations)) => violations,
+        result => panic!("unexpected result {:?}", result),
+    };
+    let paths: Vec<_> = violations
+        .iter()
+        .map(|v| match v {
+            Violation::Size { path, .. }
+            | Violation::Count { path, .. }
+            | Violation::Invalid { path, .. } => path.as_str(),
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["version", "entries[0].kind", "entries[1].magic", "tail"]
+    );
+    assert_eq!(
+        violations[2],
+        Violation::Size {
+            path: "entries[1].magic".to_owned(),
+            expected: 2,
+            actual: 3
+        }
+    );
+
+    result.version = 2;
+    result.entries[0].kind = 1;
+    result.entries[1].magic.pop();
+    result.tail.push(0x33);
+    result.entries.pop();
+
+    match result.check() {
+        Err(Error::CheckFailed(violations)) => assert_eq!(
+            violations,
+            vec![Violation::Count {
+                path: "entries".to_owned(),
+                expected: 2,
+                actual: 1
+            }]
+        ),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn write_checks() {
+    let mut result = Check::from_bytes(&input()).unwrap();
+    result.num_entries = 3;
+
+    let mut output = std::io::Cursor::new(Vec::new());
+    assert!(matches!(
+        result.write(&mut output),
+        Err(Error::CheckFailed(_))
+    ));
+    assert!(output.get_ref().is_empty());
+
+    result.write_unchecked(&mut output).unwrap();
+    assert_eq!(output.get_ref()[1], 3);
+}
+
+#[test]
+fn read_repeat_expr() {
+    let mut result = Check::from_bytes(&input()).unwrap();
+
+    let input = vec![2, 1, 2, 1, 0, 0x12, 0x34, 0, 0];
+    let mut stream = std::io::Cursor::new(&input);
+    result.read(&mut stream).unwrap();
+
+    assert_eq!(result.entries.len(), 1);
+    assert_eq!(result.entries[0].kind, 2);
+    assert!(result.tail.is_empty());
+    assert!(result.check().is_ok());
+}
+
+#[test]
+fn valid_literals() {
+    let input = [b'K', b'S', 0xff, 0xf6, 0xff, b'x', b'y', b'z', 1];
+    let valid = Valid::from_bytes(&input).unwrap();
+    assert_eq!(valid.delta, -10);
+    assert!(valid.check().is_ok());
+
+    let invalid_path = |i: usize, byte: u8| {
+        let mut input = input;
+        input[i] = byte;
+        match Valid::from_bytes(&input).map_err(Error::into_root) {
+            Err(Error::ValidationFailed(Violation::Invalid { path, .. })) => path,
+            result => panic!("unexpected result {:?}", result),
+        }
+    };
+    assert_eq!(invalid_path(1, b'T'), "magic");
+    assert_eq!(invalid_path(2, 0), "offset");
+    assert_eq!(invalid_path(3, 0xf5), "delta");
+    assert_eq!(invalid_path(7, b'y'), "name");
+    assert_eq!(invalid_path(8, 2), "kind");
+
+    let mut valid = valid;
+    valid.name = "abc".to_owned();
+    assert!(valid.check().is_ok());
+    valid.magic[1] = b'T';
+    valid.kind = Kind::Bin;
+    match valid.check() {
+        Err(Error::CheckFailed(violations)) => assert_eq!(
+            violations,
+            vec![
+                Violation::Invalid {
+                    path: "magic".to_owned(),
+                    actual: "[75, 84]".to_owned(),
+                    constraint: "== [75, 83]".to_owned(),
+                },
+                Violation::Invalid {
+                    path: "kind".to_owned(),
+                    actual: "Bin".to_owned(),
+                    constraint: "== kind::json".to_owned(),
+                },
+            ]
+        ),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
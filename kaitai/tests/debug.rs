@@ -0,0 +1,73 @@
+use kaitai::{
+    kaitai_source,
+    visit::{Field, KaitaiVisit, Value},
+    BytesStream, KaitaiStruct,
+};
+
+#[kaitai_source("formats/debug.ksy")]
+struct Debug;
+
+mod option {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/repeat.ksy", ks_debug)]
+    pub struct Repeat;
+}
+
+const INPUT: [u8; 12] = [b'D', b'B', 2, b'h', b'i', 1, 0x10, 0, 2, 0x20, 0, 0];
+
+#[test]
+fn spans() {
+    let d = Debug::from_bytes(&INPUT).unwrap();
+    let spans = d._debug();
+    assert_eq!(spans.span("magic"), Some(0..2));
+    assert_eq!(spans.span("len_name"), Some(2..3));
+    assert_eq!(spans.span("name"), Some(3..5));
+    assert_eq!(spans.span("entries"), Some(5..11));
+    assert_eq!(spans.element("entries", 0), Some(5..8));
+    assert_eq!(spans.element("entries", 1), Some(8..11));
+    assert_eq!(d.entries[1]._debug().span("value"), Some(9..11));
+}
+
+#[test]
+fn read_into() {
+    let mut d = Debug::from_bytes(&INPUT).unwrap();
+    d.read(&mut BytesStream::new(&[b'D', b'B', 0, 1, 0, 0, 2, 0, 0]))
+        .unwrap();
+    assert_eq!(d._debug().span("name"), Some(3..3));
+    assert_eq!(d._debug().element("entries", 1), Some(6..9));
+    assert_eq!(d._debug().iter().count(), 4);
+}
+
+#[test]
+fn visit() {
+    let d = Debug::from_bytes(&INPUT).unwrap();
+    let mut spans = Vec::new();
+    d.visit(&mut |field: Field<'_>| {
+        if let Value::Array(elements) = field.value {
+            spans.extend(elements.into_iter().map(|e| e.span));
+        }
+        spans.push(field.span);
+    });
+    assert_eq!(
+        spans,
+        [Some(2..3), Some(3..5), Some(5..8), Some(8..11), Some(5..11)]
+    );
+}
+
+#[test]
+fn option() {
+    let r = option::Repeat::from_bytes(&[1, 0, 1, 0, 2, 0, 0, 0, 3, 0]).unwrap();
+    assert_eq!(r._debug().span("pre_repeat"), Some(0..2));
+    assert_eq!(r._debug().element("main", 0), Some(2..10));
+    assert_eq!(r.main[0]._debug().span("tail"), Some(8..10));
+}
+
+#[test]
+fn equality() {
+    let mut shifted = vec![0];
+    shifted.extend_from_slice(&INPUT);
+    let d = Debug::from_bytes_at(&shifted, 1).unwrap();
+    assert_eq!(d._debug().span("magic"), Some(1..3));
+    assert_eq!(d, Debug::from_bytes(&INPUT).unwrap());
+}
@@ -0,0 +1,120 @@
+use kaitai::{error::Error, kaitai_source, BytesStream, KaitaiStruct, KaitaiWrite};
+
+#[kaitai_source("formats/endian.ksy")]
+struct Endian;
+
+const LE: [u8; 20] = [
+    b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0xff, 0xff, 0xff, 0xff, 2, 0, 3, 0, 0, 0,
+];
+const BE: [u8; 20] = [
+    b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 1, 0xff, 0xff, 0xff, 0xff, 0, 2, 0, 0, 0, 3,
+];
+
+#[test]
+fn switch() {
+    let le = Endian::from_bytes(&LE).unwrap();
+    let be = Endian::from_bytes(&BE).unwrap();
+    for e in [&le, &be] {
+        assert_eq!(e.header.magic, 42);
+        assert_eq!(e.header.offset, 8);
+        assert_eq!(e.header.entries[0].tag, 1);
+        assert_eq!(e.header.entries[0].value, -1);
+        assert_eq!(e.header.entries[1].tag, 2);
+        assert_eq!(e.header.entries[1].value, 3);
+    }
+    assert!(le.header._is_le());
+    assert!(le.header.entries[1]._is_le());
+    assert!(!be.header._is_le());
+    assert!(!be.header.entries[1]._is_le());
+}
+
+#[test]
+fn write() {
+    assert_eq!(Endian::from_bytes(&LE).unwrap().to_bytes().unwrap(), LE);
+    assert_eq!(Endian::from_bytes(&BE).unwrap().to_bytes().unwrap(), BE);
+}
+
+#[test]
+fn no_match() {
+    let mut input = LE;
+    input[..2].copy_from_slice(b"XX");
+    let error = Endian::from_bytes(&input).unwrap_err();
+    assert!(matches!(error.root(), Error::UndecidedEndianness));
+}
+
+mod root {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/endian_root.ksy")]
+    pub struct EndianRoot;
+}
+
+mod own {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/endian_own.ksy")]
+    pub struct EndianOwn;
+}
+
+// The count of the body is always little endian, the records switch on `_root.byte_order`.
+const ROOT_LE: [u8; 10] = [b'I', b'I', 1, 0, 7, 0, 0xfe, 0xff, 0xff, 0xff];
+const ROOT_BE: [u8; 10] = [b'M', b'M', 1, 0, 0, 7, 0xff, 0xff, 0xff, 0xfe];
+
+#[test]
+fn switch_on_root() {
+    let le = root::EndianRoot::from_bytes(&ROOT_LE).unwrap();
+    let be = root::EndianRoot::from_bytes(&ROOT_BE).unwrap();
+    for e in [&le, &be] {
+        assert_eq!(e.body.count, 1);
+        assert_eq!(e.body.records[0].tag, 7);
+        assert_eq!(e.body.records[0].value, -2);
+    }
+    assert!(le.body.records[0]._is_le());
+    assert!(!be.body.records[0]._is_le());
+
+    assert_eq!(le.to_bytes().unwrap(), ROOT_LE);
+    assert_eq!(be.to_bytes().unwrap(), ROOT_BE);
+
+    let mut read = le;
+    read.read(&mut BytesStream::new(&ROOT_BE)).unwrap();
+    assert_eq!(read, be);
+    assert_eq!(
+        root::EndianRoot::from_bytes_or_partial(&ROOT_BE).unwrap(),
+        be
+    );
+
+    let mut input = ROOT_LE;
+    input[..2].copy_from_slice(b"XX");
+    let error = root::EndianRoot::from_bytes(&input).unwrap_err();
+    assert!(matches!(error.root(), Error::UndecidedEndianness));
+}
+
+const OWN_LE: [u8; 8] = [0x49, 0x49, 42, 0, 8, 0, 0, 0];
+const OWN_BE: [u8; 8] = [0x4d, 0x4d, 0, 42, 0, 0, 0, 8];
+
+#[test]
+fn switch_on_own() {
+    let le = own::EndianOwn::from_bytes(&OWN_LE).unwrap();
+    let be = own::EndianOwn::from_bytes(&OWN_BE).unwrap();
+    for e in [&le, &be] {
+        assert_eq!(e.version, 42);
+        assert_eq!(e.directory.offset, 8);
+    }
+    assert!(le._is_le());
+    assert!(le.directory._is_le());
+    assert!(!be._is_le());
+    assert!(!be.directory._is_le());
+
+    assert_eq!(le.to_bytes().unwrap(), OWN_LE);
+    assert_eq!(be.to_bytes().unwrap(), OWN_BE);
+
+    let mut read = le;
+    read.read(&mut BytesStream::new(&OWN_BE)).unwrap();
+    assert_eq!(read, be);
+    assert_eq!(own::EndianOwn::from_bytes_or_partial(&OWN_BE).unwrap(), be);
+
+    let mut input = OWN_LE;
+    input[..2].copy_from_slice(b"XX");
+    let error = own::EndianOwn::from_bytes(&input).unwrap_err();
+    assert!(matches!(error.root(), Error::UndecidedEndianness));
+}
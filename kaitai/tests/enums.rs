@@ -1,4 +1,4 @@
-use kaitai::{kaitai_source, KaitaiStruct};
+use kaitai::{kaitai_source, KaitaiStruct, KaitaiWrite};
 
 #[kaitai_source("formats/enums.ksy")]
 struct EnumsStruct;
@@ -10,3 +10,131 @@ fn main() {
     assert_eq!(e.protocol2, IpProtocol::Tcp);
     assert_eq!(e.protocol3, IpProtocol::Udp);
 }
+
+#[test]
+fn round_trip() {
+    let e = EnumsStruct::from_bytes(&[1, 6, 17]).unwrap();
+    assert_eq!(e.to_bytes().unwrap(), vec![1, 6, 17]);
+}
+
+mod strict {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/enums.ksy", strict_enums)]
+    pub struct EnumsStruct;
+}
+
+#[kaitai_source("formats/opcodes.ksy")]
+struct Opcodes;
+
+#[test]
+fn unknown() {
+    let e = EnumsStruct::from_bytes(&[1, 2, 17]).unwrap();
+    assert_eq!(e.protocol2, IpProtocol::Unknown(2));
+    assert_eq!(e.protocol2.value(), 2);
+    assert_eq!(e.to_bytes().unwrap(), vec![1, 2, 17]);
+
+    assert_eq!(IpProtocol::from_value(6u8), IpProtocol::Tcp);
+    assert_eq!(IpProtocol::n(2u8), None);
+}
+
+#[test]
+fn strict() {
+    use kaitai::error::Error;
+
+    assert!(matches!(
+        strict::EnumsStruct::from_bytes(&[1, 2, 17]).map_err(Error::into_root),
+        Err(Error::NoEnumMatch)
+    ));
+
+    // `-strict` on an attribute overrides the option.
+    let o = Opcodes::from_bytes(&[0x10, 0x20]).unwrap();
+    assert_eq!(o.op, Opcode::UnknownValue(0x10));
+    assert_eq!(o.strict_op, Opcode::Jump);
+    assert!(matches!(
+        Opcodes::from_bytes(&[0x01, 0x10]).map_err(Error::into_root),
+        Err(Error::NoEnumMatch)
+    ));
+}
+
+#[test]
+fn names() {
+    use std::collections::HashSet;
+
+    assert_eq!(
+        IpProtocol::ALL,
+        &[IpProtocol::Icmp, IpProtocol::Tcp, IpProtocol::Udp]
+    );
+    let names: Vec<_> = IpProtocol::ALL.iter().map(|p| p.name().unwrap()).collect();
+    assert_eq!(names, ["icmp", "tcp", "udp"]);
+    assert_eq!(IpProtocol::Unknown(2).name(), None);
+
+    assert_eq!(IpProtocol::Tcp.to_string(), "tcp");
+    assert_eq!(IpProtocol::Unknown(2).to_string(), "2");
+    assert_eq!("udp".parse::<IpProtocol>().unwrap(), IpProtocol::Udp);
+    assert_eq!("2".parse::<IpProtocol>().unwrap(), IpProtocol::Unknown(2));
+    assert_eq!("6".parse::<IpProtocol>().unwrap(), IpProtocol::Tcp);
+    assert!("UDP".parse::<IpProtocol>().is_err());
+    assert!("300".parse::<IpProtocol>().is_err());
+
+    assert_eq!(Opcode::Jump.orig_id(), Some("JMP"));
+    assert_eq!(Opcode::Nop.orig_id(), None);
+    assert_eq!(Opcode::Unknown.name(), Some("unknown"));
+
+    let set: HashSet<_> = IpProtocol::ALL.iter().copied().collect();
+    assert!(set.contains(&IpProtocol::Tcp));
+}
+
+#[kaitai_source("formats/signed_enums.ksy")]
+struct SignedEnums;
+
+#[test]
+fn signed() {
+    let input = [0xfe, 0xff, 1];
+    let e = SignedEnums::from_bytes(&input).unwrap();
+    assert_eq!(e.status, Status::Timeout);
+    assert_eq!(e.color, Color::Green);
+    assert_eq!(e.to_bytes().unwrap(), input);
+
+    // The values have the types of the attributes.
+    let status: i16 = e.status.value();
+    let color: u8 = e.color.into();
+    assert_eq!((status, color), (-2, 1));
+
+    let e = SignedEnums::from_bytes(&[5, 0, 7]).unwrap();
+    assert_eq!(e.status, Status::Unknown(5));
+    assert_eq!(e.color, Color::Unknown(7));
+}
+
+#[test]
+fn try_from() {
+    use std::convert::TryFrom;
+
+    assert_eq!(Status::try_from(-1i64).unwrap(), Status::Failed);
+    assert_eq!(Status::try_from(1u64).unwrap(), Status::Pending);
+    assert!(Status::try_from(5i64).is_err());
+    assert!(Status::try_from(70000i64).is_err());
+    assert!(Color::try_from(-1i64).is_err());
+    assert_eq!(Color::try_from(0u64).unwrap(), Color::Red);
+}
+
+#[kaitai_source("formats/max_enums.ksy")]
+struct MaxEnums;
+
+#[test]
+fn max_value() {
+    let input = [0xff; 9];
+    let e = MaxEnums::from_bytes(&input).unwrap();
+    assert_eq!(e.handle, Handle::Invalid);
+    assert_eq!(e.handle.value(), u64::MAX);
+    assert_eq!(e.marker, Marker::End);
+    assert_eq!(e.to_bytes().unwrap(), input);
+
+    let e = MaxEnums::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+    assert_eq!(e.handle, Handle::Unknown(1));
+    assert_eq!(e.marker, Marker::Unknown(1));
+    let marker: u8 = e.marker.value();
+    assert_eq!(marker, 1);
+    assert!(Handle::None < Handle::Invalid);
+    assert!(Handle::Invalid < Handle::Unknown(1));
+}
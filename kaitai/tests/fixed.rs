@@ -1,4 +1,4 @@
-use kaitai::{kaitai_source, KaitaiStruct};
+use kaitai::{kaitai_source, KaitaiStruct, KaitaiWrite};
 
 #[kaitai_source("formats/fixed.ksy")]
 pub struct Fixed;
@@ -29,3 +29,15 @@ fn fixed_err() {
 
     assert!(Fixed::from_bytes(&input).is_err());
 }
+
+#[test]
+fn fixed_round_trip() {
+    let mut input: Vec<u8> = Vec::new();
+    input.extend(b"test string");
+    input.extend(&[0xab, 0xad, 0xba, 0xbe]);
+    input.extend(&[0x12, 0x34]);
+
+    let result = Fixed::from_bytes(&input).unwrap();
+
+    assert_eq!(result.to_bytes().unwrap(), input);
+}
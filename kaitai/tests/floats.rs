@@ -0,0 +1,62 @@
+use kaitai::{kaitai_source, KaitaiStruct, KaitaiWrite};
+
+/// Floats, with the attributes and visibility of this struct.
+#[kaitai_source("formats/floats.ksy")]
+#[derive(Clone, Debug)]
+pub(crate) struct Floats;
+
+mod derive {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/derive.ksy")]
+    #[derive(Clone)]
+    pub struct Derive;
+}
+
+fn input() -> Vec<u8> {
+    let mut input = Vec::new();
+    input.extend(0.5f32.to_le_bytes());
+    input.extend(1.5f64.to_le_bytes());
+    input.extend((-2.0f64).to_le_bytes());
+    input.push(1);
+    input.extend(0.25f64.to_le_bytes());
+    input
+}
+
+#[test]
+fn floats() {
+    let f = Floats::from_bytes(&input()).unwrap();
+    assert_eq!(f.scale, 0.5);
+    assert_eq!(f.origin, Point { x: 1.5, y: -2.0 });
+    assert_eq!(f.value, FloatsValue::F8(0.25));
+    assert!(f.origin.x < f.origin.y.abs());
+    assert_eq!(f.to_bytes().unwrap(), input());
+}
+
+#[test]
+fn user_derives() {
+    // `Clone` is derived for the nested types as well.
+    let f = Floats::from_bytes(&input()).unwrap();
+    let origin = f.origin.clone();
+    let value = f.value.clone();
+    assert_eq!(f.clone(), f);
+    assert_eq!((origin, value), (f.origin, f.value));
+}
+
+#[test]
+fn nested_derives() {
+    use std::collections::HashSet;
+
+    // The header derives `Hash` instead of the traits of the root type, and so does its enum.
+    let input = [1, 2, 0x34, 0x12];
+    let d = derive::Derive::from_bytes(&input).unwrap();
+    let mut headers = HashSet::new();
+    headers.insert(d.header.clone());
+    headers.insert(d.header.clone());
+    assert_eq!(headers.len(), 1);
+    assert!(headers.contains(&derive::Header {
+        version: 1,
+        kind: derive::Kind::Bin,
+    }));
+    assert_eq!(d.body.clone().value, 0x1234);
+}
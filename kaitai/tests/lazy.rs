@@ -0,0 +1,120 @@
+use kaitai::{kaitai_source, BytesStream, KaitaiStruct, KaitaiWrite};
+
+#[kaitai_source("formats/lazy.ksy")]
+struct Lazy;
+
+#[kaitai_source("formats/log.ksy", lazy)]
+struct Log;
+
+#[kaitai_source("formats/lazy_nested.ksy", lazy)]
+struct LazyNested;
+
+#[test]
+fn lazy_repeat_expr() {
+    let input = [2, 1, 0x10, 0x00, 2, 0x20, 0x00, 3, 0x30, 0x00];
+
+    let lazy = Lazy::from_bytes(&input).unwrap();
+    assert_eq!(lazy.records.start(), 1);
+    assert_eq!(lazy.records.count(), Some(2));
+
+    let mut buf = BytesStream::new(&input);
+    let records = lazy
+        .records
+        .iter(&mut buf)
+        .collect::<kaitai::error::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![
+            Record {
+                tag: 1,
+                value: 0x10
+            },
+            Record {
+                tag: 2,
+                value: 0x20
+            },
+        ]
+    );
+
+    // Only the elements are lazy, the rest is written as usual.
+    assert_eq!(lazy.to_bytes().unwrap(), vec![2]);
+}
+
+#[test]
+fn lazy_repeat_eos() {
+    let mut input = vec![1];
+    for i in 0..1000u32 {
+        input.extend_from_slice(&i.to_le_bytes());
+        input.push((i % 4) as u8);
+    }
+
+    let log = Log::from_bytes(&input).unwrap();
+    assert_eq!(log.version, 1);
+    assert_eq!(log.entries.count(), None);
+
+    let mut buf = BytesStream::new(&input);
+    let mut count = 0;
+    for (i, entry) in log.entries.iter(&mut buf).enumerate() {
+        let entry = entry.unwrap();
+        assert_eq!(entry.timestamp, i as u32);
+        assert_eq!(entry.level, (i % 4) as u8);
+        count += 1;
+    }
+    assert_eq!(count, 1000);
+
+    // The iterator stops after the first error.
+    let mut buf = BytesStream::new(&input[..8]);
+    let mut entries = log.entries.iter(&mut buf);
+    assert!(entries.next().unwrap().is_ok());
+    assert!(entries.next().unwrap().is_err());
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn lazy_read() {
+    let input = [2, 1, 0x10, 0x00, 2, 0x20, 0x00];
+    let mut lazy = Lazy::from_bytes(&input).unwrap();
+
+    let input = [1, 3, 0x30, 0x00];
+    lazy.read(&mut BytesStream::new(&input)).unwrap();
+    assert_eq!(lazy.num_records, 1);
+    assert_eq!(lazy.records.count(), Some(1));
+}
+
+#[test]
+fn lazy_nested() {
+    let input = [2, 0xaa, 0xbb, 0x77];
+    let nested = LazyNested::from_bytes(&input).unwrap();
+    assert_eq!(nested.first.items.start(), 1);
+    assert_eq!(nested.trailer, 0x77);
+
+    let mut buf = BytesStream::new(&input);
+    let items = nested
+        .first
+        .items
+        .iter(&mut buf)
+        .collect::<kaitai::error::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(items, vec![Item { value: 0xaa }, Item { value: 0xbb }]);
+}
+
+#[test]
+fn lazy_consecutive() {
+    // The stream is moved past the elements, so the next instance starts after them.
+    let input = [1, 1, 0x10, 0x00, 2, 2, 0x20, 0x00, 3, 0x30, 0x00];
+    let (first, consumed) = Lazy::from_partial_bytes(&input).unwrap();
+    assert_eq!(consumed, 4);
+    assert_eq!(first.records.count(), Some(1));
+
+    let mut reader = std::io::Cursor::new(&input[..]);
+    let all = Lazy::from_reader_iter(&mut reader)
+        .collect::<kaitai::error::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[1].records.start(), 5);
+    assert_eq!(all[1].records.count(), Some(2));
+
+    // The elements are parsed to be skipped, so missing elements fail.
+    assert!(Lazy::from_bytes(&input[4..8]).is_err());
+}
@@ -0,0 +1,110 @@
+use kaitai::{error::Error, kaitai_source, BytesStream, KaitaiStruct, ParseOptions};
+
+#[kaitai_source("formats/limits.ksy")]
+struct Limits;
+
+// 3 bytes of data, 2 items and 4 bytes of rest.
+const INPUT: [u8; 11] = [3, 0xa, 0xb, 0xc, 2, 1, 2, 0xd, 0xe, 0xf, 0x10];
+
+#[test]
+fn within_limits() {
+    let options = ParseOptions {
+        max_alloc: Some(4),
+        max_total_bytes: Some(7),
+        max_repeat: Some(2),
+        max_depth: Some(3),
+    };
+
+    let limits = Limits::from_bytes_with(&INPUT, &options).unwrap();
+    assert_eq!(limits, Limits::from_bytes(&INPUT).unwrap());
+    assert_eq!(limits.items[1].leaf.value, 2);
+}
+
+#[test]
+fn max_alloc() {
+    // The length is checked before the bytes are allocated, even though they don't exist.
+    let input = [0xff, 0, 0];
+    let options = ParseOptions {
+        max_alloc: Some(16),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Limits::from_bytes_with(&input, &options).map_err(Error::into_root),
+        Err(Error::AllocLimit {
+            size: 255,
+            limit: 16
+        })
+    ));
+
+    // Byte arrays reaching the end of the stream are checked against the remaining bytes.
+    let options = ParseOptions {
+        max_alloc: Some(3),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Limits::from_bytes_with(&INPUT, &options).map_err(Error::into_root),
+        Err(Error::AllocLimit { size: 4, limit: 3 })
+    ));
+}
+
+#[test]
+fn max_total_bytes() {
+    let options = ParseOptions {
+        max_total_bytes: Some(6),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Limits::from_bytes_with(&INPUT, &options).map_err(Error::into_root),
+        Err(Error::TotalBytesLimit { limit: 6 })
+    ));
+}
+
+#[test]
+fn max_repeat() {
+    let options = ParseOptions {
+        max_repeat: Some(1),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Limits::from_bytes_with(&INPUT, &options).map_err(Error::into_root),
+        Err(Error::RepeatLimit { count: 2, limit: 1 })
+    ));
+}
+
+#[test]
+fn max_depth() {
+    let options = ParseOptions {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        Limits::from_bytes_with(&INPUT, &options).map_err(Error::into_root),
+        Err(Error::DepthLimit { limit: 2 })
+    ));
+}
+
+#[test]
+fn from_reader_with() {
+    let options = ParseOptions {
+        max_repeat: Some(1),
+        ..Default::default()
+    };
+
+    let mut buf = BytesStream::new(&INPUT);
+    assert!(matches!(
+        Limits::from_reader_with(&mut buf, &options).map_err(Error::into_root),
+        Err(Error::RepeatLimit { .. })
+    ));
+
+    let mut reader = std::io::Cursor::new(INPUT.to_vec());
+    let mut limits = Limits::from_reader_with(&mut reader, &ParseOptions::default()).unwrap();
+
+    // Re-populating an instance is unlimited.
+    reader.set_position(0);
+    limits.read(&mut reader).unwrap();
+    assert_eq!(limits.rest, vec![0xd, 0xe, 0xf, 0x10]);
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "mmap")]
+
+use kaitai::{kaitai_source, KaitaiBorrowedStruct, KaitaiStruct, MappedFile};
+
+#[kaitai_source("formats/basic_be.ksy")]
+struct BasicBigEndian;
+
+mod borrowed {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/strings.ksy", borrowed)]
+    pub struct Strings;
+}
+
+#[test]
+fn from_file_mmap() {
+    let file = BasicBigEndian::from_file_mmap("tests/files/example.basic").unwrap();
+    assert_eq!(
+        file,
+        BasicBigEndian::from_file("tests/files/example.basic").unwrap()
+    );
+}
+
+#[test]
+fn borrowed_from_map() {
+    let path = std::env::temp_dir().join("kaitai_mmap_borrowed.strings");
+    std::fs::write(&path, b"\x05helloworld\0\x02\x01\x01\xaa\xbb\x02\xcc\xdd").unwrap();
+
+    let map = MappedFile::open(&path).unwrap();
+    let strings = borrowed::Strings::from_bytes(&map).unwrap();
+    assert_eq!(strings.name, "hello");
+    assert_eq!(strings.comment, "world");
+    assert!(std::ptr::eq(strings.name.as_ptr(), &map[1]));
+}
+
+#[test]
+fn missing_file() {
+    assert!(MappedFile::open("tests/files/missing").is_err());
+}
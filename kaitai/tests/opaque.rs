@@ -0,0 +1,80 @@
+use kaitai::{
+    error::Result,
+    kaitai_source,
+    visit::{Field, KaitaiVisit, Value},
+    KaitaiOpaque, KaitaiStream, KaitaiStruct, KaitaiWrite, KaitaiWriteStream,
+};
+
+/// A string prefixed with its length, parsed by hand.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pascal(String);
+
+impl KaitaiOpaque for Pascal {
+    fn read_opaque<S: KaitaiStream>(buf: &mut S) -> Result<Self> {
+        let len = buf.read_u1()?;
+        let bytes = buf.read_bytes(len as usize)?;
+        Ok(Pascal(
+            String::from_utf8(bytes).map_err(|e| e.utf8_error())?,
+        ))
+    }
+
+    fn write_opaque<S: KaitaiWriteStream>(&self, buf: &mut S) -> Result<()> {
+        buf.write_u1(self.0.len() as u8)?;
+        buf.write_bytes(self.0.as_bytes())
+    }
+}
+
+#[kaitai_source("formats/opaque.ksy")]
+struct Opaque;
+
+mod mapped {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/opaque_mapped.ksy", opaque_types(pascal_string = super::Pascal: Eq))]
+    pub struct Mapped;
+}
+
+const INPUT: [u8; 12] = [1, 2, b'h', b'i', 1, b'a', 1, b'b', 3, b'x', b'y', b'z'];
+
+#[test]
+fn opaque() {
+    let o = Opaque::from_bytes(&INPUT).unwrap();
+    assert_eq!(o.title, Pascal("hi".to_owned()));
+    assert_eq!(o.labels, [Pascal("a".to_owned()), Pascal("b".to_owned())]);
+    assert_eq!(o.body.inner, Pascal("xyz".to_owned()));
+    assert_eq!(o.to_bytes().unwrap(), INPUT);
+}
+
+#[test]
+fn mapped() {
+    let m = mapped::Mapped::from_bytes(&[0, 7, 2, b'o', b'k']).unwrap();
+    assert_eq!(m.count, 7);
+    assert_eq!(m.name, Pascal("ok".to_owned()));
+
+    // `Pascal` is said to implement `Eq` and `Ord`, so the types containing it derive them too.
+    fn eq_ord<T: Eq + Ord>(_: &T) {}
+    eq_ord(&m);
+}
+
+#[test]
+fn partial() {
+    let partial = Opaque::from_bytes_or_partial(&INPUT[..7]).unwrap_err();
+    assert_eq!(partial.value.title, Some(Some(Pascal("hi".to_owned()))));
+    assert_eq!(
+        partial.value.labels,
+        Some(vec![Some(Pascal("a".to_owned())), None])
+    );
+}
+
+#[test]
+fn visit() {
+    let o = Opaque::from_bytes(&INPUT).unwrap();
+    let mut opaque = Vec::new();
+    o.visit(&mut |field: Field<'_>| {
+        if let Value::Opaque(value) = field.value {
+            opaque.push(format!("{}: {:?}", field.name, value));
+        }
+    });
+    assert_eq!(opaque, ["title: Pascal(\"hi\")"]);
+}
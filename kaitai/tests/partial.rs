@@ -0,0 +1,66 @@
+use kaitai::{error::Error, kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/partial.ksy")]
+struct Container;
+
+const INPUT: [u8; 13] = [0x50, 0x41, 3, 1, 0x10, 0, 2, 0x20, 0, 3, 0x30, 0, 0xff];
+
+#[test]
+fn complete() {
+    let partial = Container::from_bytes_or_partial(&INPUT).unwrap();
+    assert_eq!(partial, Container::from_bytes(&INPUT).unwrap());
+}
+
+#[test]
+fn truncated() {
+    // The input ends in the middle of the third record.
+    let partial = Container::from_bytes_or_partial(&INPUT[..11]).unwrap_err();
+
+    assert_eq!(
+        partial.value,
+        ContainerPartial {
+            num_records: Some(3),
+            records: Some(vec![
+                RecordPartial {
+                    tag: Some(1),
+                    value: Some(0x10)
+                },
+                RecordPartial {
+                    tag: Some(2),
+                    value: Some(0x20)
+                },
+                RecordPartial {
+                    tag: Some(3),
+                    value: None
+                },
+            ]),
+            trailer: None,
+        }
+    );
+    match &partial.error {
+        Error::Parse { path, offset, .. } => {
+            assert_eq!(path, "Container.records[2].value");
+            assert_eq!(*offset, 10);
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+#[test]
+fn nothing_read() {
+    let partial = Container::from_bytes_or_partial(&[0x50, 0x42]).unwrap_err();
+
+    assert_eq!(partial.value, ContainerPartial::default());
+    assert!(matches!(
+        partial.error.root(),
+        Error::UnexpectedContents { .. }
+    ));
+}
+
+#[test]
+fn from_reader_or_partial() {
+    let mut reader = std::io::Cursor::new(INPUT[..5].to_vec());
+
+    let partial = Container::from_reader_or_partial(&mut reader).unwrap_err();
+    assert_eq!(partial.value.records.unwrap().len(), 1);
+}
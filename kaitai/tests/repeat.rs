@@ -1,4 +1,4 @@
-use kaitai::{kaitai_source, KaitaiStruct};
+use kaitai::{kaitai_source, KaitaiStruct, KaitaiWrite};
 
 #[kaitai_source("formats/repeat.ksy")]
 #[derive(Debug, PartialEq, Eq)]
@@ -34,3 +34,37 @@ fn repeat_eos() {
     assert_eq!(result.pre_repeat, 0xadde);
     assert_eq!(result.main, expected);
 }
+
+#[test]
+fn repeat_eos_round_trip() {
+    let mut input = vec![0xde, 0xad];
+    for i in 0..24 {
+        input.push(i);
+    }
+
+    let result = Repeat::from_bytes(&input).unwrap();
+
+    assert_eq!(result.to_bytes().unwrap(), input);
+}
+
+#[test]
+fn repeat_eos_read() {
+    let mut input = vec![0xde, 0xad];
+    for i in 0..24 {
+        input.push(i);
+    }
+
+    let mut result = Repeat::from_bytes(&input).unwrap();
+    let ptr = result.main.as_ptr();
+
+    let mut stream = std::io::Cursor::new(&input[..18]);
+    result.read(&mut stream).unwrap();
+    assert_eq!(result.main.len(), 2);
+    assert_eq!(result.main.as_ptr(), ptr);
+    assert_eq!(result, Repeat::from_bytes(&input[..18]).unwrap());
+
+    input[0] = 0xbe;
+    let mut stream = std::io::Cursor::new(&input);
+    result.read(&mut stream).unwrap();
+    assert_eq!(result, Repeat::from_bytes(&input).unwrap());
+}
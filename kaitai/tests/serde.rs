@@ -0,0 +1,104 @@
+#![cfg(feature = "serde")]
+
+use kaitai::{kaitai_source, KaitaiBorrowedStruct, KaitaiStruct};
+
+use serde_json::json;
+
+#[kaitai_source("formats/strings.ksy")]
+struct Strings;
+
+#[kaitai_source("formats/switch.ksy")]
+struct Message;
+
+mod base64 {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/strings.ksy", base64_bytes)]
+    pub struct Strings;
+}
+
+mod borrowed {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/strings.ksy", borrowed)]
+    pub struct Strings;
+}
+
+const STRINGS: &[u8] = b"\x03abcdef\0\x02\x00\x01\xaa\xbb\x02\xcc\xdd\xff\x00";
+
+#[test]
+fn bytes() {
+    let s = Strings::from_bytes(STRINGS).unwrap();
+    let value = serde_json::to_value(&s).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "len_name": 3,
+            "name": "abc",
+            "comment": "def",
+            "header": { "version": 2 },
+            "records": [
+                { "tag": 1, "data": "aabb" },
+                { "tag": 2, "data": "ccdd" },
+            ],
+            "rest": "ff00",
+        })
+    );
+    assert_eq!(serde_json::from_value::<Strings>(value).unwrap(), s);
+
+    let s = base64::Strings::from_bytes(STRINGS).unwrap();
+    let value = serde_json::to_value(&s).unwrap();
+    assert_eq!(value["rest"], "/wA=");
+    assert_eq!(value["records"][0]["data"], "qrs=");
+    assert_eq!(serde_json::from_value::<base64::Strings>(value).unwrap(), s);
+
+    // Types that borrow can only be serialized.
+    let s = borrowed::Strings::from_bytes(STRINGS).unwrap();
+    assert_eq!(serde_json::to_value(&s).unwrap()["rest"], "ff00");
+
+    assert!(serde_json::from_value::<Strings>(json!({
+        "len_name": 3,
+        "name": "abc",
+        "comment": "def",
+        "header": { "version": 2 },
+        "records": [],
+        "rest": "f",
+    }))
+    .is_err());
+}
+
+#[test]
+fn enums_and_switches() {
+    let m =
+        Message::from_bytes(&[2, 5, 6, 0x10, 7, 8, b'i', b'v', 1, 0, b'Z', b'M', 2, 0, 0]).unwrap();
+    let value = serde_json::to_value(&m).unwrap();
+    assert_eq!(value["kind"], "point");
+    assert_eq!(value["body"], json!({ "Point": { "x": 5, "y": 6 } }));
+    assert_eq!(value["magic"], "5a4d");
+    assert_eq!(value["state"], "off");
+    assert_eq!(serde_json::from_value::<Message>(value).unwrap(), m);
+
+    assert_eq!(serde_json::to_value(Kind::Unknown(7)).unwrap(), 7);
+    assert_eq!(
+        serde_json::from_value::<Kind>(json!(7)).unwrap(),
+        Kind::Unknown(7)
+    );
+    assert_eq!(serde_json::from_value::<Kind>(json!(1)).unwrap(), Kind::Int);
+    assert_eq!(
+        serde_json::from_value::<Kind>(json!("flags")).unwrap(),
+        Kind::Flags
+    );
+    assert!(serde_json::from_value::<Kind>(json!("bogus")).is_err());
+    assert!(serde_json::from_value::<Kind>(json!(300)).is_err());
+}
+
+#[test]
+fn partial() {
+    let partial = Strings::from_bytes_or_partial(&STRINGS[..15]).unwrap_err();
+    let value = serde_json::to_value(&partial.value).unwrap();
+    assert_eq!(
+        value["records"],
+        json!([{ "tag": 1, "data": "aabb" }, { "tag": 2, "data": null }])
+    );
+    assert_eq!(value["rest"], json!(null));
+}
@@ -0,0 +1,196 @@
+use kaitai::{error::Error, kaitai_source, KaitaiBorrowedStruct, KaitaiWrite};
+
+#[kaitai_source("formats/strings.ksy", borrowed)]
+pub struct Strings;
+
+mod owned {
+    use kaitai::kaitai_source;
+
+    #[kaitai_source("formats/strings.ksy")]
+    pub struct Strings;
+
+    #[kaitai_source("formats/padded.ksy")]
+    pub struct Padded;
+}
+
+#[kaitai_source("formats/padded.ksy", borrowed)]
+pub struct Padded;
+
+fn input() -> Vec<u8> {
+    let mut input = vec![5];
+    input.extend(b"hello");
+    input.extend(b"world\0");
+    input.extend(&[0x02, 0x01]);
+    input.extend(&[1, 0xaa, 0xbb, 2, 0xcc, 0xdd]);
+    input.extend(&[0xff; 3]);
+    input
+}
+
+#[test]
+fn borrowed() {
+    let input = input();
+    let result = Strings::from_bytes(&input).unwrap();
+
+    assert_eq!(result.name, "hello");
+    assert_eq!(result.name.as_ptr(), input[1..].as_ptr());
+    assert_eq!(result.comment, "world");
+    assert_eq!(result.header, Header { version: 0x0102 });
+    assert_eq!(result.records[1].tag, 2);
+    assert_eq!(result.records[1].data, &[0xcc, 0xdd]);
+    assert_eq!(result.records[1].data.as_ptr(), input[18..].as_ptr());
+    assert_eq!(result.rest, &[0xff; 3]);
+}
+
+#[test]
+fn borrowed_round_trip() {
+    let input = input();
+    let result = Strings::from_bytes(&input).unwrap();
+
+    assert_eq!(result.to_bytes().unwrap(), input);
+}
+
+#[test]
+fn owned() {
+    use kaitai::KaitaiStruct;
+
+    let input = input();
+    let result = owned::Strings::from_bytes(&input).unwrap();
+
+    assert_eq!(result.name, "hello".to_owned());
+    assert_eq!(result.comment, "world".to_owned());
+    assert_eq!(result.records[0].data, vec![0xaa, 0xbb]);
+    assert_eq!(result.to_bytes().unwrap(), input);
+}
+
+#[test]
+fn owned_read() {
+    use kaitai::KaitaiStruct;
+
+    let input = input();
+    let mut result = owned::Strings::from_bytes(&input).unwrap();
+    let (name, data) = (result.name.as_ptr(), result.records[1].data.as_ptr());
+
+    let mut other = vec![3];
+    other.extend(b"abc");
+    other.extend(b"de\0");
+    other.extend(&[0x04, 0x03]);
+    other.extend(&[3, 0x11, 0x22, 4, 0x33, 0x44]);
+
+    let mut stream = std::io::Cursor::new(&other);
+    result.read(&mut stream).unwrap();
+
+    assert_eq!(result.name, "abc".to_owned());
+    assert_eq!(result.name.as_ptr(), name);
+    assert_eq!(result.comment, "de".to_owned());
+    assert_eq!(result.header.version, 0x0304);
+    assert_eq!(result.records[1].data, vec![0x33, 0x44]);
+    assert_eq!(result.records[1].data.as_ptr(), data);
+    assert!(result.rest.is_empty());
+}
+
+#[test]
+fn invalid_utf8() {
+    use kaitai::KaitaiStruct;
+
+    let mut input = input();
+    input[1] = 0xff;
+
+    assert!(matches!(
+        Strings::from_bytes(&input).map_err(Error::into_root),
+        Err(Error::Utf8Error(_))
+    ));
+    assert!(matches!(
+        owned::Strings::from_bytes(&input).map_err(Error::into_root),
+        Err(Error::Utf8Error(_))
+    ));
+}
+
+#[test]
+fn partial() {
+    let input = b"\x05helloworld\0\x02\x01\x01\xaa\xbb\x02\xcc\xdd";
+
+    // The name is cut off.
+    assert!(matches!(
+        Strings::from_partial_bytes(&input[..4]),
+        Err(Error::Incomplete { needed: 2 })
+    ));
+    // The comment has no terminator yet.
+    assert!(matches!(
+        Strings::from_partial_bytes(&input[..9]),
+        Err(Error::Incomplete { .. })
+    ));
+    // The rest is read up to the end of the stream, which isn't known.
+    assert!(matches!(
+        Strings::from_partial_bytes(input),
+        Err(Error::Incomplete { .. })
+    ));
+}
+
+fn padded_input() -> Vec<u8> {
+    let mut input = b"abc\0\0\0\0\0".to_vec();
+    input.extend(b"ab  ");
+    input.extend(&[1, 2, 0, 0]);
+    input.extend(b"end");
+    input
+}
+
+#[test]
+fn padded() {
+    use kaitai::KaitaiStruct;
+
+    let input = padded_input();
+    let borrowed = Padded::from_bytes(&input).unwrap();
+    assert_eq!(borrowed.name, "abc");
+    assert_eq!(borrowed.label, "ab ");
+    assert_eq!(borrowed.tag, &[1, 2]);
+    assert_eq!(borrowed.trailer, "end");
+    assert_eq!(borrowed.to_bytes().unwrap(), input);
+
+    let owned = owned::Padded::from_bytes(&input).unwrap();
+    assert_eq!(owned.name, "abc".to_owned());
+    assert_eq!(owned.label, "ab ".to_owned());
+    assert_eq!(owned.tag, vec![1, 2]);
+    assert_eq!(owned.trailer, "end".to_owned());
+    assert_eq!(owned.to_bytes().unwrap(), input);
+}
+
+#[test]
+fn padded_read() {
+    use kaitai::KaitaiStruct;
+
+    let mut result = owned::Padded::from_bytes(&padded_input()).unwrap();
+
+    // Without terminators, the whole size is read.
+    let mut other = b"abcdefgh".to_vec();
+    other.extend(b"wxyz");
+    other.extend(&[3, 0, 4, 0]);
+    other.extend(b"x\0yz");
+
+    let mut stream = std::io::Cursor::new(&other);
+    result.read(&mut stream).unwrap();
+
+    assert_eq!(result.name, "abcdefgh".to_owned());
+    assert_eq!(result.label, "wxyz".to_owned());
+    assert_eq!(result.tag, vec![3]);
+    assert_eq!(result.trailer, "x".to_owned());
+}
+
+#[test]
+fn padded_too_long() {
+    use kaitai::{error::Violation, KaitaiStruct};
+
+    let mut result = owned::Padded::from_bytes(&padded_input()).unwrap();
+    result.name = "abcdefghi".to_owned();
+
+    match result.check() {
+        Err(Error::CheckFailed(violations)) => assert_eq!(
+            violations,
+            vec![Violation::Size {
+                path: "name".to_owned(),
+                expected: 8,
+                actual: 9
+            }]
+        ),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
@@ -0,0 +1,65 @@
+use kaitai::{error::Error, kaitai_source, KaitaiStruct, KaitaiWrite};
+
+#[kaitai_source("formats/switch.ksy")]
+struct Message;
+
+const INPUT: [u8; 16] = [
+    1, 0x78, 0x56, 0x34, 0x12, 0xff, 0x02, 0x01, b'p', b't', 3, 4, 0x50, 0x4b, 9, 1,
+];
+
+#[test]
+fn cases() {
+    let m = Message::from_bytes(&INPUT).unwrap();
+    assert_eq!(m.body, MessageBody::U4(0x12345678));
+    assert_eq!(m.payload, MessagePayload::U2(0x0102));
+    assert_eq!(m.tagged, MessageTagged::Point(Point { x: 3, y: 4 }));
+    assert_eq!(m.by_magic, MessageByMagic::U1(9));
+    assert_eq!(m.state, State::On);
+
+    let m =
+        Message::from_bytes(&[2, 5, 6, 0x10, 7, 8, b'i', b'v', 1, 0, b'Z', b'M', 2, 0, 0]).unwrap();
+    assert_eq!(m.body, MessageBody::Point(Point { x: 5, y: 6 }));
+    assert_eq!(m.payload, MessagePayload::Point(Point { x: 7, y: 8 }));
+    assert_eq!(m.tagged, MessageTagged::U2(1));
+    assert_eq!(m.by_magic, MessageByMagic::U2(2));
+    assert_eq!(m.state, State::Off);
+}
+
+#[test]
+fn default_case() {
+    let m =
+        Message::from_bytes(&[3, 1, 0, 0, 0, 0x20, 0x2a, b'i', b'v', 1, 0, 0, 0, 2, 0, 0]).unwrap();
+    assert_eq!(m.body, MessageBody::U4(1));
+    assert_eq!(m.payload, MessagePayload::U1(0x2a));
+}
+
+#[test]
+fn no_match() {
+    let mut input = INPUT;
+    input[8] = b'x';
+    match Message::from_bytes(&input) {
+        Err(Error::Parse { path, source, .. }) => {
+            assert_eq!(path, "Message.tagged");
+            assert!(matches!(*source, Error::NoSwitchMatch));
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    let partial = Message::from_bytes_or_partial(&input).unwrap_err();
+    assert_eq!(partial.value.body, Some(MessageBody::U4(0x12345678)));
+    assert_eq!(partial.value.tag.as_deref(), Some("xt"));
+    assert_eq!(partial.value.tagged, None);
+
+    // Values missing from the enum switched on match no case.
+    let mut input = INPUT;
+    input[0] = 7;
+    assert!(matches!(
+        Message::from_bytes(&input).map_err(Error::into_root),
+        Err(Error::NoSwitchMatch)
+    ));
+}
+
+#[test]
+fn round_trip() {
+    let m = Message::from_bytes(&INPUT).unwrap();
+    assert_eq!(m.to_bytes().unwrap(), INPUT);
+}
@@ -1,4 +1,4 @@
-use kaitai::{kaitai_source, KaitaiStruct};
+use kaitai::{kaitai_source, KaitaiStruct, KaitaiWrite};
 
 #[kaitai_source("formats/user_type_be.ksy")]
 struct UserTypeBigEndian;
@@ -16,3 +16,35 @@ fn user_type() {
     assert_eq!(file.tail.seq_1, 0x12c0802);
     assert_eq!(file.tail.seq_2, 0xf61f192200);
 }
+
+#[test]
+fn user_type_round_trip() {
+    let input = std::fs::read("tests/files/example.png").unwrap();
+
+    let file = UserTypeBigEndian::from_bytes(&input).unwrap();
+    let mut output = std::io::Cursor::new(Vec::new());
+    file.write(&mut output).unwrap();
+
+    assert_eq!(output.into_inner(), &input[..34]);
+}
+
+#[test]
+fn error_context() {
+    use kaitai::error::Error;
+
+    let input = std::fs::read("tests/files/example.png").unwrap();
+
+    // The input ends in the middle of `tail.seq_2`.
+    let error = UserTypeBigEndian::from_bytes(&input[..28]).unwrap_err();
+    match &error {
+        Error::Parse {
+            path, offset, ty, ..
+        } => {
+            assert_eq!(path, "UserTypeBigEndian.tail.seq_2");
+            assert_eq!(*offset, 26);
+            assert_eq!(*ty, "u8be");
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+    assert!(matches!(error.root(), Error::IoError(_)));
+}
@@ -0,0 +1,72 @@
+use kaitai::{
+    kaitai_source,
+    visit::{Field, KaitaiVisit, Value},
+    KaitaiStruct,
+};
+
+#[kaitai_source("formats/visit.ksy")]
+struct Visit;
+
+const INPUT: [u8; 19] = [
+    b'V', b'I', 2, 0, 0xfe, 0, 0, 0xc0, 0x3f, 2, b'a', b'b', 0, 0xde, 0xad, 1, 2, 3, 4,
+];
+
+fn dump(instance: &dyn KaitaiVisit, out: &mut Vec<String>) {
+    instance.visit(&mut |field: Field<'_>| {
+        let value = match field.value {
+            Value::Struct(inner) => {
+                out.push(format!("{}: {}", field.name, inner.type_name()));
+                dump(inner, out);
+                return;
+            }
+            Value::Array(elements) => {
+                out.push(format!("{}: {} elements", field.name, elements.len()));
+                for element in elements {
+                    if let Value::Struct(inner) = element.value {
+                        dump(inner, out);
+                    }
+                }
+                return;
+            }
+            value => format!("{:?}", value),
+        };
+        out.push(format!("{}: {}", field.name, value));
+    });
+}
+
+#[test]
+fn fields() {
+    let v = Visit::from_bytes(&INPUT).unwrap();
+    assert_eq!(v.type_name(), "Visit");
+
+    let mut out = Vec::new();
+    dump(&v, &mut out);
+    assert_eq!(
+        out,
+        [
+            "version: Unsigned(2)",
+            "offset: Signed(-2)",
+            "scale: Float(1.5)",
+            "color: Enum { name: Some(\"green\"), value: 2 }",
+            "name: Str(\"ab\")",
+            "data: Bytes([222, 173])",
+            "points: 2 elements",
+            "x: Unsigned(1)",
+            "y: Unsigned(2)",
+            "x: Unsigned(3)",
+            "y: Unsigned(4)",
+        ]
+    );
+}
+
+#[test]
+fn docs() {
+    let v = Visit::from_bytes(&INPUT).unwrap();
+    let mut docs = Vec::new();
+    v.visit(&mut |field: Field<'_>| {
+        assert_eq!(field.span, None);
+        docs.push((field.name, field.doc));
+    });
+    assert_eq!(docs[0], ("version", "The version of the format."));
+    assert_eq!(docs[1], ("offset", ""));
+}